@@ -0,0 +1,51 @@
+//! Benchmarks of searching a list as long as the biggest categories get.
+
+#![allow(clippy::wildcard_imports)]
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+#[allow(dead_code)]
+#[path = "../src/stateful_list.rs"]
+mod stateful_list;
+
+// NOTE: The binary has no library target, so only the part of the config the
+// list needs is stubbed in
+#[allow(dead_code)]
+mod config {
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub enum ListOverflow {
+        #[default]
+        Wrap,
+        Truncate,
+    }
+}
+
+const ITEMS: usize = 10_000;
+
+fn list() -> stateful_list::StatefulList {
+    stateful_list::StatefulList::with_items(
+        (0..ITEMS)
+            .map(|i| format!("Programming-Language-{i}-Anime-Girl-Holding-Book"))
+            .collect(),
+    )
+}
+
+fn find_all(c: &mut Criterion) {
+    let list = list();
+    c.bench_function("find_all 10k", |b| {
+        b.iter(|| list.find_all(std::hint::black_box("girl-holding")));
+    });
+    c.bench_function("find_all_prefixed 10k", |b| {
+        b.iter(|| list.find_all_prefixed(std::hint::black_box("programming-language-9")));
+    });
+    #[cfg(feature = "regex")]
+    {
+        let pattern = regex::Regex::new(r"(?i)language-\d+0-anime").unwrap();
+        c.bench_function("find_all_regex 10k", |b| {
+            b.iter(|| list.find_all_regex(std::hint::black_box(&pattern)));
+        });
+    }
+}
+
+criterion_group!(benches, find_all);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+use {image::io::Reader as ImageReader, ratatui::style::Color, std::io::Cursor};
+
+/// Size of the thumbnail the average is taken over, plenty for a single color.
+const SAMPLE_SIZE: u32 = 32;
+// Luma bounds that stay readable on both dark and light terminal backgrounds
+const MIN_LUMA: f32 = 90.0;
+const MAX_LUMA: f32 = 170.0;
+/// How much of the average color's brightness is kept for the dimmed one.
+const DIM_FACTOR: f32 = 0.3;
+
+/// Returns the average color of the image, brightened or darkened until it's
+/// readable as text and borders. `None` if the image can't be decoded.
+pub fn average_color(data: &[u8]) -> Option<Color> {
+    let [r, g, b] = average(data)?;
+
+    let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let [r, g, b] = if luma < MIN_LUMA {
+        // Blend towards white just enough to reach the minimum
+        let t = (MIN_LUMA - luma) / (255.0 - luma);
+        [r, g, b].map(|x| x + (255.0 - x) * t)
+    } else if luma > MAX_LUMA {
+        [r, g, b].map(|x| x * MAX_LUMA / luma)
+    } else {
+        [r, g, b]
+    };
+
+    Some(Color::Rgb(r as u8, g as u8, b as u8))
+}
+
+/// Returns a dark version of the average color of the image, subtle enough to
+/// sit behind it. `None` if the image can't be decoded.
+pub fn dimmed_color(data: &[u8]) -> Option<Color> {
+    let [r, g, b] = average(data)?.map(|x| x * DIM_FACTOR);
+    Some(Color::Rgb(r as u8, g as u8, b as u8))
+}
+
+fn average(data: &[u8]) -> Option<[f32; 3]> {
+    let image = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()?
+        .decode()
+        .ok()?
+        .thumbnail(SAMPLE_SIZE, SAMPLE_SIZE)
+        .into_rgb8();
+
+    let mut sum = [0u64; 3];
+    for pixel in image.pixels() {
+        for (sum, x) in sum.iter_mut().zip(pixel.0) {
+            *sum += u64::from(x);
+        }
+    }
+    let count = u64::from(image.width() * image.height()).max(1);
+    Some(sum.map(|x| f32::from((x / count) as u8)))
+}
@@ -0,0 +1,66 @@
+use {
+    crate::http,
+    color_eyre::{
+        eyre::{bail, Context, ContextCompat},
+        Result,
+    },
+    std::{fmt, time::Duration},
+    tokio::time,
+};
+
+/// How long the check may hold the startup up before it's given up on.
+const TIMEOUT: Duration = Duration::from_secs(3);
+/// The newest major version of the API the `aghpb` crate knows the responses
+/// of.
+pub const SUPPORTED_API_MAJOR: u64 = 1;
+
+/// The version the API reports, as in `1.8.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ApiVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl ApiVersion {
+    /// Whether the responses may have changed in ways the `aghpb` crate
+    /// doesn't handle.
+    pub fn is_supported(self) -> bool { self.major <= SUPPORTED_API_MAJOR }
+
+    fn parse(version: &str) -> Option<ApiVersion> {
+        let mut parts = version.trim_start_matches('v').split('.');
+        let mut next = || parts.next().map_or(Some(0), |x| x.parse().ok());
+        Some(ApiVersion {
+            major: next()?,
+            minor: next()?,
+            patch: next()?,
+        })
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Asks the API for its version, checking the response has the shape it's
+/// expected to. Gives up after [`TIMEOUT`].
+pub async fn check_api_compatibility() -> Result<ApiVersion> {
+    let info = time::timeout(TIMEOUT, http::client().info())
+        .await
+        .wrap_err("the API didn't answer in time")?
+        .wrap_err("unable to retrieve the API info")?;
+
+    let Some(info) = info.as_object() else {
+        bail!("unexpected response from the API: not an object");
+    };
+    // NOTE: Older deployments call it `version`
+    let version = info
+        .get("api_version")
+        .or_else(|| info.get("version"))
+        .and_then(|x| x.as_str())
+        .wrap_err("unexpected response from the API: no version")?;
+    ApiVersion::parse(version)
+        .wrap_err_with(|| format!("unexpected response from the API: invalid version `{version}`"))
+}
@@ -0,0 +1,32 @@
+use {
+    image::{imageops::FilterType, io::Reader as ImageReader, DynamicImage},
+    ratatui::style::Color,
+    ratatui_image::protocol::StatefulProtocol,
+    std::io::Cursor,
+};
+
+/// The image is shrunk to this size before blurring, nothing survives the blur
+/// that would need more.
+const BLUR_SAMPLE_SIZE: u32 = 64;
+const BLUR_SIGMA: f32 = 4.0;
+
+/// Fills the part of the image pane the image leaves empty.
+pub enum Background {
+    Dim(Color),
+    // A blurred copy of the image, stretched over the image pane
+    Blur(Box<dyn StatefulProtocol>),
+}
+
+/// Returns a heavily blurred copy of the image stretched to `width` by
+/// `height` pixels. `None` if the image can't be decoded.
+pub fn blur(data: &[u8], width: u32, height: u32) -> Option<DynamicImage> {
+    let image = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()?
+        .decode()
+        .ok()?
+        .thumbnail(BLUR_SAMPLE_SIZE, BLUR_SAMPLE_SIZE)
+        .blur(BLUR_SIGMA);
+    // NOTE: Scaling up with a smooth filter keeps the blur from turning blocky
+    Some(image.resize_exact(width.max(1), height.max(1), FilterType::Triangle))
+}
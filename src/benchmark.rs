@@ -0,0 +1,117 @@
+use {
+    crate::{cli::OutputFormat, http},
+    color_eyre::{eyre::Context, Result, Section},
+    serde::Serialize,
+    std::time::Duration,
+    tokio::time::Instant,
+};
+
+/// How many categories are searched and have their first image fetched.
+const CATEGORIES: usize = 5;
+
+/// Latencies of one API call, in milliseconds.
+#[derive(Serialize)]
+struct Timings {
+    samples: Vec<f64>,
+    p50: f64,
+    p95: f64,
+}
+
+impl Timings {
+    fn new(samples: &[Duration]) -> Timings {
+        let samples = samples
+            .iter()
+            .map(|x| x.as_secs_f64() * 1000.0)
+            .collect::<Vec<_>>();
+        let mut sorted = samples.clone();
+        sorted.sort_by(f64::total_cmp);
+        Timings {
+            p50: percentile(&sorted, 50),
+            p95: percentile(&sorted, 95),
+            samples,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report {
+    categories: Timings,
+    search: Timings,
+    get_book: Timings,
+}
+
+/// Times the API calls made while browsing, without starting the TUI, and
+/// prints the results.
+pub async fn run(output: OutputFormat) -> Result<()> {
+    let start = Instant::now();
+    let categories = http::client()
+        .categories()
+        .await
+        .wrap_err("unable to retrieve the categories")
+        .suggestion("check your internet connectivity")?;
+    let categories_latency = start.elapsed();
+
+    let mut search = Vec::new();
+    let mut get_book = Vec::new();
+    for category in categories.into_iter().take(CATEGORIES) {
+        let start = Instant::now();
+        // NOTE: Same query the app lists a category with
+        let images = http::client()
+            .search(" ", Some(&category), None)
+            .await
+            .wrap_err_with(|| format!("unable to retrieve image list of category: `{category}`"))
+            .suggestion("check your internet connectivity")?;
+        search.push(start.elapsed());
+
+        let Some(image) = images.first() else {
+            continue;
+        };
+        let start = Instant::now();
+        http::client()
+            .get_id(&image.search_id)
+            .await
+            .wrap_err("unable to retrieve book data")
+            .suggestion("check your internet connectivity")?;
+        get_book.push(start.elapsed());
+    }
+
+    let report = Report {
+        categories: Timings::new(&[categories_latency]),
+        search: Timings::new(&search),
+        get_book: Timings::new(&get_book),
+    };
+    match output {
+        OutputFormat::Text => {
+            println!(
+                "{:<12}{:>8}{:>12}{:>12}",
+                "call", "samples", "p50 (ms)", "p95 (ms)"
+            );
+            for (call, timings) in [
+                ("categories", &report.categories),
+                ("search", &report.search),
+                ("get_book", &report.get_book),
+            ] {
+                println!(
+                    "{call:<12}{:>8}{:>12.1}{:>12.1}",
+                    timings.samples.len(),
+                    timings.p50,
+                    timings.p95,
+                );
+            }
+        },
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string(&report).wrap_err("unable to serialize the benchmark")?
+        ),
+    }
+    Ok(())
+}
+
+/// Nearest rank percentile of the sorted samples, `0.0` without any.
+fn percentile(sorted: &[f64], percent: usize) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
@@ -0,0 +1,93 @@
+use {
+    color_eyre::{
+        eyre::{Context, ContextCompat},
+        Result, Section,
+    },
+    std::{fs, io, path::PathBuf},
+};
+
+/// A hidden image.
+#[derive(Debug, Clone)]
+pub struct Hidden {
+    pub search_id: String,
+    pub name: String,
+}
+
+/// Images the user never wants to see again, left out of every list, in the
+/// order they were hidden. Persisted across runs as one tab separated
+/// identifier and name per line.
+#[derive(Debug, Default)]
+pub struct Blacklist {
+    items: Vec<Hidden>,
+}
+
+impl Blacklist {
+    pub fn load() -> Result<Blacklist> {
+        let items = match fs::read_to_string(path()?) {
+            Ok(contents) => contents
+                .lines()
+                .filter_map(|x| x.split_once('\t'))
+                .map(|(search_id, name)| Hidden {
+                    search_id: search_id.to_owned(),
+                    name: name.to_owned(),
+                })
+                .collect(),
+            // Nothing has been hidden yet
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).wrap_err("unable to read the blacklist"),
+        };
+        Ok(Blacklist { items })
+    }
+
+    pub fn contains(&self, search_id: &str) -> bool {
+        self.items.iter().any(|x| x.search_id == search_id)
+    }
+
+    pub fn is_empty(&self) -> bool { self.items.is_empty() }
+
+    /// Returns the names of the hidden images in their order.
+    pub fn names(&self) -> impl Iterator<Item = &str> { self.items.iter().map(|x| x.name.as_str()) }
+
+    pub fn hide(&mut self, search_id: &str, name: &str) -> Result<()> {
+        if self.contains(search_id) {
+            return Ok(());
+        }
+        self.items.push(Hidden {
+            search_id: search_id.to_owned(),
+            name: name.to_owned(),
+        });
+        self.save()
+    }
+
+    /// Shows the image at the position in lists again.
+    pub fn unhide(&mut self, i: usize) -> Result<Hidden> {
+        let hidden = self.items.remove(i);
+        self.save()?;
+        Ok(hidden)
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = path()?;
+        // Impossible for the path to not have a parent as it's built from the data
+        // directory
+        fs::create_dir_all(path.parent().unwrap())
+            .wrap_err("unable to create the data directory")?;
+
+        let contents = self
+            .items
+            .iter()
+            .map(|x| format!("{}\t{}", x.search_id, x.name))
+            .collect::<Vec<_>>()
+            .join("\n");
+        fs::write(path, contents)
+            .wrap_err("unable to write the blacklist")
+            .suggestion("verify the permissions of your data directory")
+    }
+}
+
+fn path() -> Result<PathBuf> {
+    let mut path = dirs::data_dir().wrap_err("unable to locate data directory")?;
+    path.push("aghpb-tui");
+    path.push("blacklist");
+    Ok(path)
+}
@@ -0,0 +1,76 @@
+/// The upstream repository the books come from, matched ignoring case.
+const REPO: &str = "anime-girls-holding-programming-books";
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp"];
+
+/// A book a link points at.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BookLink {
+    // A file in the upstream repository, named after the book
+    Repo { category: String, name: String },
+    // The image of a book served by the API
+    Api { search_id: String },
+}
+
+/// Parses a link to a book in the upstream repository on GitHub, or to the
+/// image of a book on the API. `None` if `input` isn't a link at all, the
+/// reason it can't be opened if it's a link to anything else.
+pub fn parse(input: &str, api_url: &str) -> Option<Result<BookLink, &'static str>> {
+    let input = input.trim();
+    let rest = input
+        .strip_prefix("https://")
+        .or_else(|| input.strip_prefix("http://"))?;
+
+    if let Some(search_id) = input
+        .strip_prefix(api_url)
+        .and_then(|x| x.strip_prefix("/v1/get/id/"))
+    {
+        return Some(match urlencoding::decode(search_id) {
+            Ok(search_id) if !search_id.is_empty() => Ok(BookLink::Api {
+                search_id: search_id.into_owned(),
+            }),
+            _ => Err("the link doesn't name a book"),
+        });
+    }
+
+    // NOTE: Query strings and fragments, like `?raw=true`, don't change the file
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let segments = path
+        .split('/')
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>();
+    // Past the owner and the repository, both hosts have the branch in the path,
+    // github.com has a `blob` or `raw` before it
+    let file_path = match (
+        host.strip_prefix("www.").unwrap_or(host),
+        segments.as_slice(),
+    ) {
+        ("github.com", [_, repo, "blob" | "raw", _, file_path @ ..])
+        | ("raw.githubusercontent.com", [_, repo, _, file_path @ ..])
+            if repo.eq_ignore_ascii_case(REPO) =>
+        {
+            file_path
+        },
+        _ => return Some(Err("the link isn't to a book in the repository or the API")),
+    };
+
+    let [category, file] = file_path else {
+        return Some(Err("the link isn't to a book inside a category"));
+    };
+    let Some((stem, extension)) = file.rsplit_once('.') else {
+        return Some(Err("the link isn't to an image"));
+    };
+    if !IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str()) {
+        return Some(Err("the link isn't to an image"));
+    }
+    let (Ok(category), Ok(stem)) = (urlencoding::decode(category), urlencoding::decode(stem))
+    else {
+        return Some(Err("the link isn't valid UTF-8"));
+    };
+
+    Some(Ok(BookLink::Repo {
+        category: category.into_owned(),
+        // The API names books after their files, with spaces for underscores
+        name: stem.replace('_', " "),
+    }))
+}
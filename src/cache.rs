@@ -0,0 +1,145 @@
+use {
+    aghpb::BookData,
+    color_eyre::{
+        eyre::{Context, ContextCompat},
+        Result,
+    },
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
+    std::{
+        collections::HashMap,
+        fs, io,
+        path::PathBuf,
+        sync::Arc,
+        time::{Duration, SystemTime},
+    },
+};
+
+/// A cached item along with when it was fetched, so every item goes stale on
+/// its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry<T> {
+    pub data: T,
+    pub fetched_at: SystemTime,
+}
+
+impl<T> CacheEntry<T> {
+    /// Wraps data that was just fetched.
+    pub fn new(data: T) -> CacheEntry<T> {
+        CacheEntry {
+            data,
+            fetched_at: SystemTime::now(),
+        }
+    }
+
+    pub fn age(&self) -> Duration {
+        // A timestamp in the future means the clock changed, treat it as fresh
+        self.fetched_at.elapsed().unwrap_or_default()
+    }
+
+    /// Whether the item is older than `max_age`. Nothing goes stale without a
+    /// maximum age.
+    pub fn is_stale(&self, max_age: Option<Duration>) -> bool {
+        max_age.is_some_and(|x| self.age() > x)
+    }
+}
+
+/// The parts of [`BookData`] worth caching, as it can't be serialized itself.
+#[derive(Serialize, Deserialize)]
+struct CachedBook {
+    name: String,
+    category: String,
+    date_added: String,
+    search_id: String,
+    commit_url: String,
+    commit_author: String,
+}
+
+// NOTE: This is the format the API sends the date in, `BookData::from_json`
+// parses it back
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S%z";
+
+impl From<&BookData> for CachedBook {
+    fn from(book: &BookData) -> Self {
+        CachedBook {
+            name: book.name.clone(),
+            category: book.category.clone(),
+            date_added: book.date_added.format(DATE_FORMAT).to_string(),
+            search_id: book.search_id.clone(),
+            commit_url: book.commit_url.clone(),
+            commit_author: book.commit_author.clone(),
+        }
+    }
+}
+
+impl From<CachedBook> for BookData {
+    fn from(book: CachedBook) -> Self {
+        BookData::from_json(HashMap::from([
+            ("name".to_owned(), book.name),
+            ("category".to_owned(), book.category),
+            ("date_added".to_owned(), book.date_added),
+            ("search_id".to_owned(), book.search_id),
+            ("commit_url".to_owned(), book.commit_url),
+            ("commit_author".to_owned(), book.commit_author),
+        ]))
+    }
+}
+
+pub fn save_categories(categories: &[String]) -> Result<()> {
+    save("categories.json", &CacheEntry::new(categories))
+}
+
+/// Returns the cached categories, `None` if nothing is cached.
+pub fn load_categories() -> Result<Option<CacheEntry<Vec<String>>>> { load("categories.json") }
+
+pub fn save_image_list(category: &str, images: &[Arc<BookData>]) -> Result<()> {
+    let books = images
+        .iter()
+        .map(|x| CachedBook::from(&**x))
+        .collect::<Vec<_>>();
+    save(&image_list_name(category), &CacheEntry::new(books))
+}
+
+/// Returns the cached image list of the category, `None` if nothing is
+/// cached.
+pub fn load_image_list(category: &str) -> Result<Option<CacheEntry<Vec<Arc<BookData>>>>> {
+    let entry: Option<CacheEntry<Vec<CachedBook>>> = load(&image_list_name(category))?;
+    Ok(entry.map(|x| CacheEntry {
+        data: x
+            .data
+            .into_iter()
+            .map(|x| Arc::new(BookData::from(x)))
+            .collect(),
+        fetched_at: x.fetched_at,
+    }))
+}
+
+fn image_list_name(category: &str) -> String {
+    // NOTE: Category names can contain path separators
+    format!("lists/{}.json", category.replace(['/', '\\'], "_"))
+}
+
+fn save<T: Serialize>(name: &str, entry: &CacheEntry<T>) -> Result<()> {
+    let path = path(name)?;
+    // Impossible for the path to not have a parent as it's built from the cache
+    // directory
+    fs::create_dir_all(path.parent().unwrap()).wrap_err("unable to create the cache directory")?;
+    // Impossible for this to explode as the entries only hold plain data
+    fs::write(path, serde_json::to_string(entry).unwrap()).wrap_err("unable to write the cache")
+}
+
+fn load<T: DeserializeOwned>(name: &str) -> Result<Option<CacheEntry<T>>> {
+    let contents = match fs::read_to_string(path(name)?) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).wrap_err("unable to read the cache"),
+    };
+    // NOTE: A broken entry is as good as a missing one, it gets fetched again
+    Ok(serde_json::from_str(&contents).ok())
+}
+
+fn path(name: &str) -> Result<PathBuf> {
+    let mut path = dirs::cache_dir().wrap_err("unable to locate cache directory")?;
+    path.push("aghpb-tui");
+    path.push(name);
+    Ok(path)
+}
@@ -0,0 +1,61 @@
+use {
+    clap::{Parser, ValueEnum},
+    std::path::PathBuf,
+};
+
+/// A TUI client for Anime Girls Holding Programming Books.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Display a local image file instead of browsing the API
+    #[arg(long, value_name = "PATH")]
+    pub file: Option<PathBuf>,
+    /// Open the image with this name once the categories load, in its
+    /// category
+    #[arg(long, value_name = "NAME", conflicts_with = "file")]
+    pub open: Option<String>,
+    /// Log how long every API call and image encode takes to `latency.log`
+    /// in the cache directory, as one JSON object per line
+    #[arg(long)]
+    pub verbose: bool,
+    /// Probe the terminal for its graphics protocol and font size instead of
+    /// using what was found on a previous run
+    #[arg(long)]
+    pub redetect: bool,
+    /// Width and height in pixels of a terminal cell, for terminals that
+    /// don't report it. Overrides the `font_size` option
+    #[arg(long, value_name = "WxH", value_parser = parse_font_size)]
+    pub font_size: Option<[u16; 2]>,
+    /// Print the config in effect, with the options from the command line
+    /// and the environment applied, as TOML and exit
+    #[arg(long, conflicts_with_all = ["file", "open", "benchmark"])]
+    pub export_config: bool,
+    /// Print the summary of the downloads as JSON when quitting, even with
+    /// the `exit_summary` option off
+    #[arg(long, conflicts_with_all = ["export_config", "benchmark"])]
+    pub exit_summary_json: bool,
+    /// Time the API calls made while browsing and print the results, without
+    /// starting the TUI
+    #[arg(long, conflicts_with = "file")]
+    pub benchmark: bool,
+    /// How the benchmark results are printed
+    #[arg(long, value_enum, default_value_t, requires = "benchmark")]
+    pub output: OutputFormat,
+}
+
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    /// A table for reading
+    #[default]
+    Text,
+    /// JSON for scripts
+    Json,
+}
+
+fn parse_font_size(s: &str) -> Result<[u16; 2], String> {
+    let parse = |x: &str| x.parse::<u16>().ok().filter(|x| *x > 0);
+    s.split_once('x')
+        .and_then(|(w, h)| Some([parse(w)?, parse(h)?]))
+        .ok_or_else(|| format!("expected a size like `10x20`, got `{s}`"))
+}
@@ -0,0 +1,13 @@
+use {
+    base64::{engine::general_purpose::STANDARD, Engine},
+    std::io::{self, Write},
+};
+
+/// Copies the text to the system clipboard through the terminal, using the
+/// OSC 52 escape sequence. Works over SSH too, as long as the terminal allows
+/// it.
+pub fn copy(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()
+}
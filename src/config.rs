@@ -0,0 +1,501 @@
+use {
+    crate::spinner::Spinner,
+    color_eyre::{
+        eyre::{Context, ContextCompat},
+        Result, Section,
+    },
+    ratatui::widgets::BorderType,
+    ratatui_image::picker::ProtocolType,
+    serde::{Deserialize, Serialize},
+    std::{collections::HashMap, env, fmt, fs, io, ops::RangeInclusive, path::PathBuf},
+};
+
+const ASPECT_CORRECTION_RANGE: RangeInclusive<f32> = 0.5..=5.0;
+const TICK_MS_RANGE: RangeInclusive<u64> = 10..=1000;
+const MIN_RELEVANCE_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+const LIST_WIDTH_PERCENT_RANGE: RangeInclusive<u16> = 10..=90;
+// NOTE: The screensaver runs unattended, so it's kept from hammering the API
+const MIN_SCREENSAVER_INTERVAL_SECS: u64 = 5;
+
+/// User configuration, read from `config.toml` in the `aghpb-tui` config
+/// directory. Missing options fall back to their defaults.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Load the selected image as soon as an image list is shown: the one
+    /// that was selected when the category was last left, the first one
+    /// otherwise. Used to be called `auto_load_first`.
+    #[serde(alias = "auto_load_first")]
+    pub auto_load_first_image: bool,
+    /// Save downloads into `aghpb/<category>` inside the downloads directory.
+    pub download_by_category: bool,
+    /// Longest sides in pixels of the smaller copies `D` saves next to a
+    /// download, like `[256, 512]`. The copies keep the aspect ratio of the
+    /// image and are suffixed with their size, as in `name-256px.jpeg`.
+    pub download_sizes: Vec<u32>,
+    /// How many times taller a terminal cell is than it is wide, used to fit
+    /// images to the image pane. Decrease it if images leave empty space on
+    /// their sides, increase it if they leave empty space above and below.
+    /// Defaults to `2.0` for halfblocks and `2.15` for the other protocols.
+    pub aspect_correction: Option<f32>,
+    /// Width and height in pixels of a terminal cell, like `[10, 20]`, for
+    /// terminals that don't report it. Also gives the aspect correction when
+    /// `aspect_correction` isn't set. `--font-size` overrides it.
+    pub font_size: Option<[u16; 2]>,
+    /// The graphics protocol images are drawn with, `"halfblocks"`,
+    /// `"sixel"`, `"kitty"` or `"iterm2"`. Detected from the terminal by
+    /// default.
+    pub protocol: Option<Protocol>,
+    /// How much of the window's width the list takes up, in percent from 10
+    /// to 90. The image pane takes up the rest.
+    pub list_width_percent: u16,
+    /// Options for a single terminal, in `[profiles.<name>]` tables named
+    /// after `$TERM_PROGRAM`, or `$TERM` where that's unset, like
+    /// `[profiles.xterm-kitty]`. They override the options above in that
+    /// terminal, other terminals keep using those.
+    pub profiles: HashMap<String, Profile>,
+    /// How often, in milliseconds, the screen is refreshed while something is
+    /// loading or animating. When idle, the app waits for input for up to
+    /// 2 seconds at a time instead.
+    pub tick_ms: u64,
+    /// Images taking more memory to decode than this many megapixels of RGBA
+    /// aren't loaded unless asked to with `!`, as decoding them can take up
+    /// gigabytes of memory.
+    pub max_image_megapixels: u32,
+    /// Ask before loading images bigger than this many bytes, which helps on
+    /// metered connections. Off by default.
+    pub confirm_above_bytes: Option<u64>,
+    /// Fetch the shown image list again every this many minutes, so new books
+    /// show up without having to refresh by hand. Off by default.
+    pub auto_refresh_minutes: Option<u64>,
+    /// Show the categories and image lists cached on disk right away, and
+    /// fetch the ones older than this many minutes again in the background.
+    /// Off by default, everything is fetched on access instead.
+    pub cache_max_age_minutes: Option<u64>,
+    /// Search results matching the query worse than this, from `0.0` to
+    /// `1.0`, are collapsed at the end of the list until expanded with `x`.
+    /// `0.0` shows every result.
+    pub min_relevance: f32,
+    /// Pressing Enter on an empty search lists every image, in the category the
+    /// search is limited to if it is. Off by default, leaving Enter to do
+    /// nothing until something is typed.
+    pub empty_search_browses_all: bool,
+    /// Print where the images downloaded in the session were saved, and which
+    /// downloads failed, once the app quits. Only printed if anything was
+    /// downloaded.
+    pub exit_summary: bool,
+    /// Ring the terminal bell when a download finishes or fails.
+    pub bell: bool,
+    /// Color the image pane and the list highlight after the average color of
+    /// the shown image.
+    pub dynamic_accent: bool,
+    /// What to do with list items too long for their list, `"wrap"` them onto
+    /// more rows or `"truncate"` them with an ellipsis.
+    pub list_overflow: ListOverflow,
+    /// The borders drawn around the panes and popups.
+    pub border_style: BorderStyleConfig,
+    /// The animation next to what's loading: `"dots"`, `"line"`, `"braille"`,
+    /// `"none"`, or a list of frames like `["◐", "◓", "◑", "◒"]`. It moves on
+    /// a frame every `tick_ms`.
+    pub spinner: Spinner,
+    /// The text shown while the categories or an image load.
+    pub loading_text: String,
+    /// What fills the image pane around the image: `"none"`, a `"dim"` shade
+    /// of the image's average color, or a `"blur"`ry copy of the image
+    /// stretched over the pane. Blurring falls back to dimming with
+    /// halfblocks.
+    pub image_background: ImageBackground,
+    /// How many times an image that failed to load over a flaky connection
+    /// is fetched again before giving up. `0` gives up right away.
+    pub image_load_retries: u8,
+    /// The API to browse, for self-hosted instances. Defaults to
+    /// `https://api.devgoldy.xyz/aghpb`.
+    pub api_url: Option<String>,
+    /// The User-Agent sent with every request, overridden by the
+    /// `AGHPB_TUI_USER_AGENT` environment variable. Defaults to
+    /// `aghpb-tui/<version>`.
+    pub user_agent: Option<String>,
+    /// Extra headers sent with every request, like an API key. Each
+    /// `AGHPB_TUI_HEADER_<NAME>` environment variable adds one more, named
+    /// after `<NAME>` with underscores turned into dashes, so tokens can be
+    /// kept out of the config.
+    pub headers: HashMap<String, String>,
+    /// The proxy every request goes through, like `http://localhost:8080` or
+    /// `socks5://localhost:1080`. Defaults to the `HTTPS_PROXY` and
+    /// `HTTP_PROXY` environment variables.
+    pub proxy: Option<String>,
+    /// How long a request may take before it's given up on, in seconds.
+    /// Requests wait as long as they have to by default.
+    pub request_timeout_secs: Option<u64>,
+    /// Show how long API calls and image encodes take on average in the
+    /// status bar, to tell a slow API from a slow terminal. The time the shown
+    /// image spent being fetched, decoded and drawn follows.
+    pub show_latency: bool,
+    /// Stop redrawing the spinners and advancing the screensaver while the
+    /// terminal doesn't have focus, picking up again once it does. Only
+    /// terminals reporting focus changes support it.
+    pub pause_when_unfocused: bool,
+    /// With `pause_when_unfocused`, also stop loading category lists and
+    /// next images ahead of time while the terminal doesn't have focus.
+    pub stop_prefetching_when_unfocused: bool,
+    /// Show random books fullscreen once the app has been left alone for this
+    /// many minutes, until a key is pressed. Off by default.
+    pub screensaver_minutes: Option<u64>,
+    /// How many seconds each book is shown for by the screensaver, at least
+    /// 5.
+    pub screensaver_interval_secs: u64,
+    /// Close the download popup and the API version warning on their own after
+    /// this many seconds, a key still closes them sooner. Off by default, they
+    /// wait for a key.
+    pub popup_timeout_secs: Option<u64>,
+}
+
+/// The options a terminal's profile can override.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub protocol: Option<Protocol>,
+    pub aspect_correction: Option<f32>,
+    pub font_size: Option<[u16; 2]>,
+    pub list_width_percent: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Halfblocks,
+    Sixel,
+    Kitty,
+    Iterm2,
+}
+
+impl From<Protocol> for ProtocolType {
+    fn from(protocol: Protocol) -> Self {
+        match protocol {
+            Protocol::Halfblocks => ProtocolType::Halfblocks,
+            Protocol::Sixel => ProtocolType::Sixel,
+            Protocol::Kitty => ProtocolType::Kitty,
+            Protocol::Iterm2 => ProtocolType::Iterm2,
+        }
+    }
+}
+
+/// What fills the part of the image pane the image leaves empty.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageBackground {
+    #[default]
+    None,
+    Dim,
+    Blur,
+}
+
+/// The border of each kind of block, in a `[border_style]` table.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BorderStyleConfig {
+    /// The panes and the loading box.
+    pub main_blocks: BorderKind,
+    pub popup_blocks: BorderKind,
+    /// The pane the arrow keys act on.
+    pub focused_block: BorderKind,
+}
+
+impl Default for BorderStyleConfig {
+    fn default() -> Self {
+        Self {
+            main_blocks: BorderKind::Thick,
+            popup_blocks: BorderKind::Thick,
+            focused_block: BorderKind::Thick,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BorderKind {
+    #[serde(alias = "single")]
+    Plain,
+    Rounded,
+    Double,
+    Thick,
+}
+
+impl From<BorderKind> for BorderType {
+    fn from(kind: BorderKind) -> Self {
+        match kind {
+            BorderKind::Plain => BorderType::Plain,
+            BorderKind::Rounded => BorderType::Rounded,
+            BorderKind::Double => BorderType::Double,
+            BorderKind::Thick => BorderType::Thick,
+        }
+    }
+}
+
+/// How list items too long for their list are fit into it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListOverflow {
+    #[default]
+    Wrap,
+    Truncate,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            auto_load_first_image: true,
+            download_by_category: false,
+            download_sizes: Vec::new(),
+            aspect_correction: None,
+            font_size: None,
+            protocol: None,
+            list_width_percent: 30,
+            profiles: HashMap::new(),
+            tick_ms: 250,
+            max_image_megapixels: 50,
+            confirm_above_bytes: None,
+            auto_refresh_minutes: None,
+            cache_max_age_minutes: None,
+            min_relevance: 0.0,
+            empty_search_browses_all: false,
+            exit_summary: true,
+            bell: false,
+            dynamic_accent: false,
+            list_overflow: ListOverflow::Wrap,
+            border_style: BorderStyleConfig::default(),
+            spinner: Spinner::default(),
+            loading_text: "Loading...".to_owned(),
+            image_background: ImageBackground::None,
+            image_load_retries: 3,
+            api_url: None,
+            user_agent: None,
+            headers: HashMap::new(),
+            proxy: None,
+            request_timeout_secs: None,
+            show_latency: false,
+            pause_when_unfocused: false,
+            stop_prefetching_when_unfocused: false,
+            screensaver_minutes: None,
+            popup_timeout_secs: None,
+            screensaver_interval_secs: 15,
+        }
+    }
+}
+
+/// An option that parsed fine but holds a value the app can't work with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigError {
+    AspectCorrection(f32),
+    TickMs(u64),
+    MaxImageMegapixels,
+    AutoRefreshMinutes,
+    MinRelevance(f32),
+    ScreensaverMinutes,
+    ScreensaverIntervalSecs(u64),
+    DownloadSizes,
+    FontSize,
+    ListWidthPercent(u16),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::AspectCorrection(value) => write!(
+                f,
+                "`aspect_correction` must be between {} and {}, got {value}",
+                ASPECT_CORRECTION_RANGE.start(),
+                ASPECT_CORRECTION_RANGE.end(),
+            ),
+            ConfigError::TickMs(value) => write!(
+                f,
+                "`tick_ms` must be between {} and {}, got {value}",
+                TICK_MS_RANGE.start(),
+                TICK_MS_RANGE.end(),
+            ),
+            ConfigError::MaxImageMegapixels => {
+                write!(f, "`max_image_megapixels` must be at least 1")
+            },
+            ConfigError::AutoRefreshMinutes => {
+                write!(f, "`auto_refresh_minutes` must be at least 1")
+            },
+            ConfigError::MinRelevance(value) => write!(
+                f,
+                "`min_relevance` must be between {} and {}, got {value}",
+                MIN_RELEVANCE_RANGE.start(),
+                MIN_RELEVANCE_RANGE.end(),
+            ),
+            ConfigError::ScreensaverMinutes => {
+                write!(f, "`screensaver_minutes` must be at least 1")
+            },
+            ConfigError::ScreensaverIntervalSecs(value) => write!(
+                f,
+                "`screensaver_interval_secs` must be at least {MIN_SCREENSAVER_INTERVAL_SECS}, \
+                 got {value}",
+            ),
+            ConfigError::DownloadSizes => write!(f, "`download_sizes` must all be at least 1"),
+            ConfigError::FontSize => write!(f, "`font_size` must be at least 1 by 1"),
+            ConfigError::ListWidthPercent(value) => write!(
+                f,
+                "`list_width_percent` must be between {} and {}, got {value}",
+                LIST_WIDTH_PERCENT_RANGE.start(),
+                LIST_WIDTH_PERCENT_RANGE.end(),
+            ),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file. Options with invalid values are reset to their
+    /// defaults and returned alongside the config, so the user can be told
+    /// about them.
+    pub fn load() -> Result<(Config, Vec<ConfigError>)> {
+        let path = path()?;
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            // No config file means everything is left at its default
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok((Config::default(), Vec::new()))
+            },
+            Err(e) => return Err(e).wrap_err("unable to read the config file"),
+        };
+
+        let mut config: Config = toml::from_str(&contents)
+            .wrap_err_with(|| format!("unable to parse the config file: `{}`", path.display()))
+            .suggestion("check the config file for typos and invalid values")?;
+        // NOTE: Applied before validating, so the profile's values are checked too
+        config.apply_profile();
+
+        let errors = config.validate();
+        let default = Config::default();
+        for error in &errors {
+            match error {
+                ConfigError::AspectCorrection(_) => {
+                    config.aspect_correction = default.aspect_correction;
+                },
+                ConfigError::TickMs(_) => config.tick_ms = default.tick_ms,
+                ConfigError::MaxImageMegapixels => {
+                    config.max_image_megapixels = default.max_image_megapixels;
+                },
+                ConfigError::AutoRefreshMinutes => {
+                    config.auto_refresh_minutes = default.auto_refresh_minutes;
+                },
+                ConfigError::MinRelevance(_) => config.min_relevance = default.min_relevance,
+                ConfigError::ScreensaverMinutes => {
+                    config.screensaver_minutes = default.screensaver_minutes;
+                },
+                ConfigError::ScreensaverIntervalSecs(_) => {
+                    config.screensaver_interval_secs = default.screensaver_interval_secs;
+                },
+                ConfigError::DownloadSizes => config.download_sizes.clear(),
+                ConfigError::FontSize => config.font_size = default.font_size,
+                ConfigError::ListWidthPercent(_) => {
+                    config.list_width_percent = default.list_width_percent;
+                },
+            }
+        }
+
+        Ok((config, errors))
+    }
+
+    /// Returns the config as TOML with every option spelled out, as a
+    /// starting point for a config file. Header values and the credentials of
+    /// the proxy are redacted.
+    pub fn to_toml_string(&self) -> String {
+        // Impossible for this to explode as the config only holds plain data
+        let mut table = toml::Table::try_from(self).unwrap();
+        if let Some(toml::Value::Table(headers)) = table.get_mut("headers") {
+            for (_, value) in headers.iter_mut() {
+                *value = "<redacted>".into();
+            }
+        }
+        if let Some(toml::Value::String(proxy)) = table.get_mut("proxy") {
+            if let Some((scheme, rest)) = proxy.split_once("://") {
+                if let Some((_, host)) = rest.rsplit_once('@') {
+                    *proxy = format!("{scheme}://<redacted>@{host}");
+                }
+            }
+        }
+
+        format!(
+            "# Generated by aghpb-tui --export-config\n{}",
+            toml::to_string_pretty(&table).unwrap()
+        )
+    }
+
+    /// Overrides the options with the ones of the profile named after the
+    /// terminal, if there's one.
+    fn apply_profile(&mut self) {
+        let terminal = env::var("TERM_PROGRAM")
+            .or_else(|_| env::var("TERM"))
+            .unwrap_or_default();
+        let Some(profile) = self.profiles.get(&terminal).copied() else {
+            return;
+        };
+
+        self.protocol = profile.protocol.or(self.protocol);
+        self.aspect_correction = profile.aspect_correction.or(self.aspect_correction);
+        self.font_size = profile.font_size.or(self.font_size);
+        self.list_width_percent = profile
+            .list_width_percent
+            .unwrap_or(self.list_width_percent);
+    }
+
+    /// The `aspect_correction` option, or the one `font_size` gives when only
+    /// that is set, so images are fit to the same cells they're drawn in.
+    pub fn cell_aspect_correction(&self) -> Option<f32> {
+        self.aspect_correction
+            .or(self.font_size.map(|[w, h]| f32::from(h) / f32::from(w)))
+    }
+
+    /// Returns every option holding a value outside of its valid range.
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+
+        if let Some(value) = self.aspect_correction {
+            // NOTE: `contains` is false for NaN, so it's caught here too
+            if !ASPECT_CORRECTION_RANGE.contains(&value) {
+                errors.push(ConfigError::AspectCorrection(value));
+            }
+        }
+        if !TICK_MS_RANGE.contains(&self.tick_ms) {
+            errors.push(ConfigError::TickMs(self.tick_ms));
+        }
+        if self.max_image_megapixels == 0 {
+            errors.push(ConfigError::MaxImageMegapixels);
+        }
+        if self.auto_refresh_minutes == Some(0) {
+            errors.push(ConfigError::AutoRefreshMinutes);
+        }
+        if !MIN_RELEVANCE_RANGE.contains(&self.min_relevance) {
+            errors.push(ConfigError::MinRelevance(self.min_relevance));
+        }
+        if self.screensaver_minutes == Some(0) {
+            errors.push(ConfigError::ScreensaverMinutes);
+        }
+        if self.screensaver_interval_secs < MIN_SCREENSAVER_INTERVAL_SECS {
+            errors.push(ConfigError::ScreensaverIntervalSecs(
+                self.screensaver_interval_secs,
+            ));
+        }
+        if self.download_sizes.contains(&0) {
+            errors.push(ConfigError::DownloadSizes);
+        }
+        if self.font_size.is_some_and(|x| x.contains(&0)) {
+            errors.push(ConfigError::FontSize);
+        }
+        if !LIST_WIDTH_PERCENT_RANGE.contains(&self.list_width_percent) {
+            errors.push(ConfigError::ListWidthPercent(self.list_width_percent));
+        }
+
+        errors
+    }
+}
+
+fn path() -> Result<PathBuf> {
+    let mut path = dirs::config_dir().wrap_err("unable to locate config directory")?;
+    path.push("aghpb-tui");
+    path.push("config.toml");
+    Ok(path)
+}
@@ -0,0 +1,266 @@
+use {
+    bytes::Bytes,
+    ratatui::widgets::*,
+    serde_json::json,
+    std::{
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, AtomicU8, Ordering},
+            Arc,
+        },
+    },
+    tokio::{sync::Semaphore, task::AbortHandle},
+};
+
+/// How many downloads get written to disk at the same time, the rest wait for
+/// their turn.
+const MAX_CONCURRENT_DOWNLOADS: usize = 2;
+
+/// Progress of a download, shared between the download task and the UI.
+#[derive(Debug, Default)]
+pub struct Progress {
+    started: AtomicBool,
+    percent: AtomicU8,
+}
+
+impl Progress {
+    pub fn start(&self) { self.started.store(true, Ordering::Relaxed); }
+
+    pub fn set(&self, percent: u8) { self.percent.store(percent, Ordering::Relaxed); }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DownloadStatus {
+    Waiting,
+    Downloading(u8),
+    Done,
+    Failed(String),
+}
+
+pub struct Download {
+    pub id: usize,
+    pub name: String,
+    pub category: String,
+    pub path: PathBuf,
+    // Stores the raw bytes so the download can be retried
+    pub data: Bytes,
+    // The longest side of the resized copy this writes, `None` for the book
+    // itself
+    pub thumbnail: Option<u32>,
+    pub progress: Arc<Progress>,
+    // Set once the download task finishes
+    pub result: Option<Result<(), String>>,
+    pub handle: Option<AbortHandle>,
+}
+
+impl Download {
+    pub fn status(&self) -> DownloadStatus {
+        match &self.result {
+            Some(Ok(())) => DownloadStatus::Done,
+            Some(Err(reason)) => DownloadStatus::Failed(reason.clone()),
+            None if self.progress.started.load(Ordering::Relaxed) => {
+                DownloadStatus::Downloading(self.progress.percent.load(Ordering::Relaxed))
+            },
+            None => DownloadStatus::Waiting,
+        }
+    }
+
+    /// Stops the download, removing what it wrote so far.
+    pub fn cancel(&self) {
+        if let Some(handle) = &self.handle {
+            handle.abort();
+        }
+        if self.result.is_none() {
+            // NOTE: Nothing was written yet if it didn't start, or it's gone already
+            let _ = std::fs::remove_file(partial_path(&self.path));
+        }
+    }
+
+    /// Clears the outcome of a previous attempt so the download can be
+    /// started again.
+    pub fn reset(&mut self) {
+        self.progress = Arc::default();
+        self.result = None;
+        self.handle = None;
+    }
+}
+
+/// Where a download is written until it's complete, so one cut short is never
+/// taken for the book itself.
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    PathBuf::from(partial)
+}
+
+pub struct DownloadQueue {
+    pub items: Vec<Download>,
+    pub state: ListState,
+    // Limits how many download tasks write at once
+    pub permits: Arc<Semaphore>,
+    next_id: usize,
+}
+
+impl Default for DownloadQueue {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            state: ListState::default(),
+            permits: Arc::new(Semaphore::new(MAX_CONCURRENT_DOWNLOADS)),
+            next_id: 0,
+        }
+    }
+}
+
+impl DownloadQueue {
+    pub fn push(
+        &mut self,
+        name: String,
+        category: String,
+        path: PathBuf,
+        data: Bytes,
+    ) -> &mut Download {
+        self.items.push(Download {
+            id: self.next_id,
+            name,
+            category,
+            path,
+            data,
+            thumbnail: None,
+            progress: Arc::default(),
+            result: None,
+            handle: None,
+        });
+        self.next_id += 1;
+
+        if self.state.selected().is_none() {
+            self.state.select(Some(0));
+        }
+
+        // Impossible for this to explode as an item was just pushed
+        self.items.last_mut().unwrap()
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Download> {
+        self.items.iter_mut().find(|x| x.id == id)
+    }
+
+    pub fn selected_mut(&mut self) -> Option<&mut Download> {
+        self.state.selected().and_then(|i| self.items.get_mut(i))
+    }
+
+    pub fn remove_selected(&mut self) -> Option<Download> {
+        let i = self.state.selected()?;
+        let download = self.items.remove(i);
+
+        if self.items.is_empty() {
+            self.state.select(None);
+        } else if i >= self.items.len() {
+            self.state.select(Some(self.items.len() - 1));
+        }
+
+        Some(download)
+    }
+
+    pub fn is_empty(&self) -> bool { self.items.is_empty() }
+
+    /// Returns the number of active (waiting or downloading), failed and done
+    /// downloads.
+    pub fn summary(&self) -> (usize, usize, usize) {
+        self.items
+            .iter()
+            .fold((0, 0, 0), |(active, failed, done), x| match x.result {
+                None => (active + 1, failed, done),
+                Some(Err(_)) => (active, failed + 1, done),
+                Some(Ok(())) => (active, failed, done + 1),
+            })
+    }
+
+    /// Lists where the finished downloads were saved and why the failed ones
+    /// failed, as JSON if `json` is set. `None` if nothing was downloaded.
+    pub fn exit_summary(&self, json: bool) -> Option<String> {
+        if self.items.is_empty() {
+            return None;
+        }
+        let done = self
+            .items
+            .iter()
+            .filter(|x| x.status() == DownloadStatus::Done)
+            .map(|x| x.path.display().to_string())
+            .collect::<Vec<_>>();
+        let failed = self
+            .items
+            .iter()
+            .filter_map(|x| match x.status() {
+                DownloadStatus::Failed(reason) => Some((x.name.as_str(), reason)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // Cut short by quitting
+        let unfinished = self.items.len() - done.len() - failed.len();
+
+        if json {
+            let failed = failed
+                .iter()
+                .map(|(name, reason)| json!({ "name": name, "reason": reason }))
+                .collect::<Vec<_>>();
+            return Some(
+                json!({ "downloaded": done, "failed": failed, "unfinished": unfinished })
+                    .to_string(),
+            );
+        }
+
+        let mut lines = vec![format!("Downloaded {} image(s):", done.len())];
+        lines.extend(done.iter().map(|x| format!("  {x}")));
+        if !failed.is_empty() {
+            lines.push(format!("Failed {}:", failed.len()));
+            lines.extend(
+                failed
+                    .iter()
+                    .map(|(name, reason)| format!("  {name}: {reason}")),
+            );
+        }
+        if unfinished > 0 {
+            lines.push(format!("Cancelled by quitting: {unfinished}"));
+        }
+        Some(lines.join("\n"))
+    }
+
+    pub fn get_list<'a>(&self) -> List<'a> {
+        let rows = self.items.iter().map(|x| {
+            let status = match x.status() {
+                DownloadStatus::Waiting => "waiting".to_owned(),
+                DownloadStatus::Downloading(percent) => format!("downloading {percent}%"),
+                DownloadStatus::Done => "done".to_owned(),
+                DownloadStatus::Failed(reason) => format!("failed: {reason}"),
+            };
+            match x.thumbnail {
+                Some(size) => format!("{} {size}px ({status})", x.name),
+                None => format!("{} ({status})", x.name),
+            }
+        });
+        List::new(rows)
+    }
+
+    pub fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) if i < self.items.len() - 1 => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) if i > 0 => i - 1,
+            _ => self.items.len() - 1,
+        };
+        self.state.select(Some(i));
+    }
+}
@@ -0,0 +1,54 @@
+use {
+    crate::list_source::ListSource,
+    aghpb::BookData,
+    color_eyre::{
+        eyre::{Context, ContextCompat},
+        Result,
+    },
+    std::{fs, path::PathBuf, sync::Arc},
+};
+
+/// Returns the URL the image of the book can be downloaded from.
+pub fn book_url(api_url: &str, search_id: &str) -> String {
+    format!("{api_url}/v1/get/id/{}", urlencoding::encode(search_id))
+}
+
+/// Writes the images as a CSV file to the download directory, and returns its
+/// path.
+pub fn write_csv(source: &ListSource, images: &[Arc<BookData>], api_url: &str) -> Result<PathBuf> {
+    let mut csv = String::from("name,category,date_added,search_id,url\n");
+    csv.extend(images.iter().map(|x| {
+        let fields = [
+            x.name.clone(),
+            x.category.clone(),
+            x.date_added.to_rfc3339(),
+            x.search_id.clone(),
+            book_url(api_url, &x.search_id),
+        ];
+        let row = fields.iter().map(|x| csv_field(x)).collect::<Vec<_>>();
+        format!("{}\n", row.join(","))
+    }));
+
+    let name = match source {
+        ListSource::Category(category) => category.clone(),
+        ListSource::Search(query, None) => format!("search {query}"),
+        ListSource::Search(query, Some(category)) => format!("search {query} in {category}"),
+        ListSource::Tag(tag, _) => format!("tag {tag}"),
+    };
+    // NOTE: Category names and queries can contain path separators
+    let name = name.replace(['/', '\\'], "_");
+
+    let mut path = dirs::download_dir().wrap_err("unable to locate download directory")?;
+    path.push(format!("aghpb {name}.csv"));
+    fs::write(&path, csv).wrap_err_with(|| format!("unable to write `{}`", path.display()))?;
+    Ok(path)
+}
+
+/// Quotes the field if it contains anything CSV treats specially.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
@@ -0,0 +1,130 @@
+use {
+    crate::export,
+    color_eyre::{
+        eyre::{Context, ContextCompat},
+        Result, Section,
+    },
+    std::{
+        fs, io,
+        path::{Path, PathBuf},
+    },
+};
+
+/// A favorited image.
+#[derive(Debug, Clone)]
+pub struct Favorite {
+    pub search_id: String,
+    pub name: String,
+}
+
+/// The user's favorite images in the order they were added, persisted across
+/// runs as one tab separated identifier and name per line.
+#[derive(Debug, Default)]
+pub struct Favorites {
+    items: Vec<Favorite>,
+}
+
+impl Favorites {
+    pub fn load() -> Result<Favorites> {
+        let items = match fs::read_to_string(path("favorites")?) {
+            Ok(contents) => contents
+                .lines()
+                .filter_map(|x| x.split_once('\t'))
+                .map(|(search_id, name)| Favorite {
+                    search_id: search_id.to_owned(),
+                    name: name.to_owned(),
+                })
+                .collect(),
+            // Nothing has been favorited yet
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).wrap_err("unable to read the favorites file"),
+        };
+        Ok(Favorites { items })
+    }
+
+    pub fn contains(&self, search_id: &str) -> bool {
+        self.items.iter().any(|x| x.search_id == search_id)
+    }
+
+    pub fn is_empty(&self) -> bool { self.items.is_empty() }
+
+    /// Returns the names of the favorites in their order.
+    pub fn names(&self) -> impl Iterator<Item = &str> { self.items.iter().map(|x| x.name.as_str()) }
+
+    /// Swaps the favorites at the two positions.
+    pub fn swap(&mut self, a: usize, b: usize) -> Result<()> {
+        self.items.swap(a, b);
+        self.save()
+    }
+
+    /// Adds the image to the favorites, or removes it if it already is one.
+    /// Returns whether the image is a favorite afterwards.
+    pub fn toggle(&mut self, search_id: &str, name: &str) -> Result<bool> {
+        let favorited = if let Some(i) = self.items.iter().position(|x| x.search_id == search_id) {
+            self.items.remove(i);
+            false
+        } else {
+            self.items.push(Favorite {
+                search_id: search_id.to_owned(),
+                name: name.to_owned(),
+            });
+            true
+        };
+        self.save()?;
+        Ok(favorited)
+    }
+
+    /// Writes a shell script that downloads every favorite with `curl`, and
+    /// returns its path.
+    pub fn export_script(&self, api_url: &str) -> Result<PathBuf> {
+        let mut script = String::from("#!/bin/sh\n");
+        script.extend(self.items.iter().map(|x| {
+            format!(
+                "curl -fL -o {} {}\n",
+                shell_quote(&format!("{}.jpeg", x.name)),
+                shell_quote(&export::book_url(api_url, &x.search_id)),
+            )
+        }));
+
+        let path = path("bookmarks.sh")?;
+        write(&path, &script).wrap_err("unable to write the bookmarks script")?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+                .wrap_err("unable to make the bookmarks script executable")?;
+        }
+
+        Ok(path)
+    }
+
+    fn save(&self) -> Result<()> {
+        let contents = self
+            .items
+            .iter()
+            .map(|x| format!("{}\t{}", x.search_id, x.name))
+            .collect::<Vec<_>>()
+            .join("\n");
+        write(&path("favorites")?, &contents).wrap_err("unable to write the favorites file")
+    }
+}
+
+fn write(path: &Path, contents: &str) -> Result<()> {
+    // Impossible for the path to not have a parent as it's built from the data
+    // directory
+    fs::create_dir_all(path.parent().unwrap()).wrap_err("unable to create the data directory")?;
+    fs::write(path, contents)
+        .wrap_err_with(|| format!("unable to write `{}`", path.display()))
+        .suggestion("verify the permissions of your data directory")
+}
+
+/// Quotes the string so that the shell passes it through untouched.
+fn shell_quote(s: &str) -> String { format!("'{}'", s.replace('\'', r"'\''")) }
+
+fn path(name: &str) -> Result<PathBuf> {
+    let mut path = dirs::data_dir().wrap_err("unable to locate data directory")?;
+    path.push("aghpb-tui");
+    path.push(name);
+    Ok(path)
+}
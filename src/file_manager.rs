@@ -0,0 +1,25 @@
+use std::{
+    io,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// Opens the directory in the system file manager, without waiting for it to
+/// close.
+pub fn open(dir: &Path) -> io::Result<()> {
+    #[cfg(target_os = "macos")]
+    let program = "open";
+    #[cfg(windows)]
+    let program = "explorer";
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let program = "xdg-open";
+
+    // NOTE: Anything the file manager prints would end up drawn over the TUI
+    Command::new(program)
+        .arg(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
@@ -0,0 +1,47 @@
+use std::cmp::Reverse;
+
+/// Returns how well `query` matches `candidate`, or `None` if the characters
+/// of `query` don't all appear in `candidate` in order. Matching is case
+/// insensitive.
+pub fn score(query: &str, candidate: &str) -> Option<usize> {
+    let mut candidate = candidate.chars().flat_map(char::to_lowercase).enumerate();
+    let mut score = 0;
+    let mut last = None;
+
+    for q in query.chars().flat_map(char::to_lowercase) {
+        let (i, _) = candidate.find(|(_, c)| *c == q)?;
+        // Consecutive matches and a match at the very start are worth more
+        score += match last {
+            Some(last) if last + 1 == i => 3,
+            None if i == 0 => 2,
+            _ => 1,
+        };
+        last = Some(i);
+    }
+
+    Some(score)
+}
+
+/// Returns how well `query` matches `candidate`, from `0.0` when it doesn't
+/// match at all to `1.0` when `candidate` starts with `query`.
+#[allow(clippy::cast_precision_loss)]
+pub fn relevance(query: &str, candidate: &str) -> f32 {
+    let len = query.chars().flat_map(char::to_lowercase).count();
+    if len == 0 {
+        return 1.0;
+    }
+    // The best possible score, every match consecutive from the very start
+    let best = 3 * len - 1;
+    score(query, candidate).map_or(0.0, |x| (x as f32 / best as f32).min(1.0))
+}
+
+/// Returns the items matching `query`, best matches first. Items that match
+/// equally well keep their original order.
+pub fn filter<'a>(query: &str, items: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut matches = items
+        .into_iter()
+        .filter_map(|x| score(query, x).map(|score| (score, x)))
+        .collect::<Vec<_>>();
+    matches.sort_by_key(|(score, _)| Reverse(*score));
+    matches.into_iter().map(|(_, x)| x.to_owned()).collect()
+}
@@ -0,0 +1,116 @@
+use ratatui::{prelude::*, widgets::*};
+
+/// Every keybinding, shown in the help popup.
+pub const HELP_TABLE: &[(&str, &str)] = &[
+    ("Up / Down", "Move through the list"),
+    ("Right / Enter", "Open the category or load the image"),
+    (
+        "! / Shift+Enter",
+        "Load the image even if it's over the size limit",
+    ),
+    ("Left", "Back to the categories"),
+    (
+        "T",
+        "Browse by tags split out of the category names, with how many categories carry each",
+    ),
+    (
+        "< / >",
+        "Prefetch the lists of fewer or more categories from the top, up to 10",
+    ),
+    ("s / /", "Search"),
+    ("Tab / Shift+Tab", "Pick a search suggestion"),
+    ("Ctrl+T", "Search in the current category or in all of them"),
+    ("d", "Download the image, press again to overwrite"),
+    (
+        "D",
+        "Save resized copies of the image in the `download_sizes`",
+    ),
+    ("y", "Copy the image as a data URI"),
+    (
+        "Y",
+        "Copy the image to a temp file, removed on quit, and copy its path",
+    ),
+    ("f", "Favorite the selected image"),
+    ("t", "Take a note about the image, Ctrl+S saves it"),
+    (
+        "F",
+        "Show the favorites, Shift+Up / Shift+Down reorders them",
+    ),
+    ("E", "Export the favorites as a download script"),
+    ("H", "Hide the image from every list for good"),
+    ("B", "Show the hidden images, u un-hides one"),
+    ("X", "Export the image list as CSV"),
+    ("x", "Expand the weaker search results"),
+    (
+        "L",
+        "Switch between the last category list and the last search results",
+    ),
+    (
+        "o",
+        "Sort the images by name, author or date added, or back as they came",
+    ),
+    (
+        "] / [",
+        "Jump to the next / previous book not downloaded yet",
+    ),
+    ("n", "Preview the next image in a corner"),
+    ("1", "Show the image at its real size"),
+    ("h / j / k / l", "Pan the image shown at its real size"),
+    ("c", "Clear the seen marks of the image list"),
+    ("R", "Refresh the image list"),
+    ("F5", "Reload the image"),
+    ("Ctrl+Z", "Undo the last favorite or download"),
+    (
+        "Ctrl+R",
+        "Start over from the categories, keeping the favorites and notes",
+    ),
+    ("Ctrl+B", "Pin the image to compare it with the next one"),
+    ("m", "Mark the image to compare, up to two"),
+    (
+        "C",
+        "Compare the marked images side by side, + / - zooms and s swaps them",
+    ),
+    ("Tab", "Switch between the list and the image panes"),
+    (
+        "0",
+        "Reset the sort, the view modes, the pin and the marks to their defaults",
+    ),
+    (
+        "Arrows on an image",
+        "Show the previous or next image in the list",
+    ),
+    ("Ctrl+P", "Jump to a category"),
+    (
+        "Ctrl+<letter>",
+        "Jump to the next category starting with the letter",
+    ),
+    (
+        "Ctrl+F",
+        "Find in the list, Up / Down for the other matches",
+    ),
+    ("Ctrl+Q", "Show the downloads"),
+    ("r", "Retry the selected download"),
+    ("x / Delete", "Cancel the selected download"),
+    ("F12", "Copy what the app is doing, for bug reports"),
+    ("?", "Show this help"),
+    ("q / Ctrl+C", "Quit"),
+];
+
+pub fn table<'a>() -> Table<'a> {
+    let rows = HELP_TABLE.iter().enumerate().map(|(i, (key, action))| {
+        let row = Row::new([key.green().bold(), Span::from(*action)]);
+        // Alternate the background so long rows are easier to follow
+        if i % 2 == 0 {
+            row
+        } else {
+            row.on_dark_gray()
+        }
+    });
+    // Impossible for this to explode as the table isn't empty
+    let key_width = HELP_TABLE.iter().map(|(key, _)| key.len()).max().unwrap() as u16;
+
+    Table::new(rows, [Constraint::Length(key_width), Constraint::Fill(1)])
+        .header(Row::new(["Key", "Action"]).bold().underlined())
+        .column_spacing(2)
+        .highlight_style(Style::default().reversed())
+}
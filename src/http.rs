@@ -0,0 +1,311 @@
+use {
+    crate::{config::Config, export},
+    aghpb::{Book, BookData},
+    color_eyre::{eyre::Context, Result, Section},
+    reqwest::{
+        header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH},
+        Proxy, StatusCode,
+    },
+    std::{collections::HashMap, env, fmt, sync::OnceLock, time::Duration},
+};
+
+const DEFAULT_API_URL: &str = "https://api.devgoldy.xyz/aghpb";
+const DEFAULT_USER_AGENT: &str = concat!("aghpb-tui/", env!("CARGO_PKG_VERSION"));
+/// Overrides the `user_agent` option.
+const USER_AGENT_VAR: &str = "AGHPB_TUI_USER_AGENT";
+/// Variables starting with this add a header named after the rest of the
+/// variable, with underscores turned into dashes.
+const HEADER_VAR_PREFIX: &str = "AGHPB_TUI_HEADER_";
+
+/// How long an idle connection is kept open for the next request.
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+const TCP_KEEPALIVE: Duration = Duration::from_mins(1);
+
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// Talks to the API. Stands in for `aghpb::Client`, which doesn't let the
+/// requests it sends be customized.
+#[derive(Debug)]
+pub struct Client {
+    pub api_url: String,
+    client: reqwest::Client,
+}
+
+/// Why a request failed.
+#[derive(Debug)]
+pub enum Error {
+    Request(reqwest::Error),
+    // The API answered, but with an error
+    Api { status: StatusCode, message: String },
+    Json(serde_json::Error),
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Request(e) => write!(f, "{e}"),
+            Error::Api { status, message } => write!(f, "API Error: [{status}] {message}"),
+            Error::Json(e) => write!(f, "unexpected response from the API: {e}"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self { Error::Request(e) }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self { Error::Json(e) }
+}
+
+impl Error {
+    /// Whether the request is worth trying again: timeouts, dropped
+    /// connections and server errors are, missing books aren't.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Request(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.is_request()
+                    || e.is_body()
+                    || e.status().is_some_and(|x| x.is_server_error())
+            },
+            Error::Api { status, .. } => status.is_server_error(),
+            Error::Json(_) => false,
+        }
+    }
+}
+
+/// Builds the client every request goes through.
+pub fn init(config: &Config) -> Result<()> {
+    let client = build(config)?;
+    // NOTE: Only the first call is ever made, from `main`
+    let _ = CLIENT.set(client);
+    Ok(())
+}
+
+/// Builds a client with the user agent and the headers from the config and
+/// the environment. Its connections are kept alive and pooled, and HTTP/2 is
+/// used when the API offers it, so requests don't each pay for a new TLS
+/// handshake.
+fn build(config: &Config) -> Result<Client> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(user_agent(config))
+        .default_headers(header_map(&headers(config))?)
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .tcp_keepalive(TCP_KEEPALIVE);
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(
+            Proxy::all(proxy)
+                .wrap_err_with(|| format!("invalid proxy: `{proxy}`"))
+                .suggestion("check the `proxy` option")?,
+        );
+    }
+    if let Some(secs) = config.request_timeout_secs {
+        builder = builder.timeout(Duration::from_secs(secs));
+    }
+    let client = builder
+        .build()
+        .wrap_err("unable to build the HTTP client")?;
+    let api_url = config
+        .api_url
+        .clone()
+        .unwrap_or_else(|| DEFAULT_API_URL.to_owned());
+    Ok(Client {
+        api_url: api_url.trim_end_matches('/').to_owned(),
+        client,
+    })
+}
+
+/// The User-Agent requests are sent with, from the environment, the config
+/// or the default.
+pub fn user_agent(config: &Config) -> String {
+    env::var(USER_AGENT_VAR)
+        .ok()
+        .or_else(|| config.user_agent.clone())
+        .unwrap_or_else(|| DEFAULT_USER_AGENT.to_owned())
+}
+
+/// The headers from the config along with the ones from the environment.
+pub fn headers(config: &Config) -> HashMap<String, String> {
+    let mut headers = config.headers.clone();
+    // NOTE: The environment wins, so tokens don't have to be written to the config
+    headers.extend(env::vars().filter_map(|(name, value)| {
+        let name = name.strip_prefix(HEADER_VAR_PREFIX)?.replace('_', "-");
+        Some((name, value))
+    }));
+    headers
+}
+
+fn header_map(headers: &HashMap<String, String>) -> Result<HeaderMap> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let name = HeaderName::try_from(name)
+                .wrap_err_with(|| format!("invalid header name: `{name}`"))
+                .suggestion("check the `headers` option and the AGHPB_TUI_HEADER_ variables")?;
+            let value = HeaderValue::try_from(value)
+                .wrap_err_with(|| format!("invalid value for the header: `{name}`"))?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// Returns the client built by [`init`], or a default one if it wasn't
+/// called.
+pub fn client() -> &'static Client {
+    CLIENT.get_or_init(|| Client {
+        api_url: DEFAULT_API_URL.to_owned(),
+        client: reqwest::Client::new(),
+    })
+}
+
+impl Client {
+    /// Uses the `/v1/categories` endpoint.
+    pub async fn categories(&self) -> Result<Vec<String>, Error> {
+        let response = self.get("/v1/categories").send().await?;
+        Ok(serde_json::from_str(&ok(response).await?.text().await?)?)
+    }
+
+    /// Uses the `/v1/info` endpoint.
+    pub async fn info(&self) -> Result<serde_json::Value, Error> {
+        let response = self.get("/v1/info").send().await?;
+        Ok(serde_json::from_str(&ok(response).await?.text().await?)?)
+    }
+
+    /// Uses the `/v1/search` endpoint.
+    pub async fn search(
+        &self,
+        query: &str,
+        category: Option<&str>,
+        limit: Option<u8>,
+    ) -> Result<Vec<BookData>, Error> {
+        let mut request = self.get("/v1/search").query(&[("query", query)]);
+        if let Some(category) = category {
+            request = request.query(&[("category", category)]);
+        }
+        if let Some(limit) = limit {
+            request = request.query(&[("limit", limit)]);
+        }
+
+        let response = ok(request.send().await?).await?;
+        let books: Vec<HashMap<String, String>> = serde_json::from_str(&response.text().await?)?;
+        Ok(books.into_iter().map(BookData::from_json).collect())
+    }
+
+    /// Uses the `/v1/get/id` endpoint.
+    pub async fn get_id(&self, search_id: &str) -> Result<Book, Error> {
+        let response = self
+            .client
+            .get(export::book_url(&self.api_url, search_id))
+            .send()
+            .await?;
+        let response = ok(response).await?;
+        let headers = response.headers().clone();
+        Ok(Book::from_response(headers, response.bytes().await?))
+    }
+
+    /// Uses the `/v1/random` endpoint.
+    pub async fn random(&self) -> Result<Book, Error> {
+        let response = ok(self.get("/v1/random").send().await?).await?;
+        let headers = response.headers().clone();
+        Ok(Book::from_response(headers, response.bytes().await?))
+    }
+
+    /// Asks for the size of the book in bytes without downloading it. `None`
+    /// if the API doesn't say.
+    pub async fn book_size(&self, search_id: &str) -> Option<u64> {
+        let url = export::book_url(&self.api_url, search_id);
+        let response = self.client.head(url).send().await.ok()?;
+        // NOTE: `Response::content_length` describes the body, which a HEAD response
+        // doesn't have, so the header is read instead
+        response
+            .headers()
+            .get(CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client.get(format!("{}{path}", self.api_url))
+    }
+}
+
+/// Turns an error response into an [`Error::Api`], using the message the API
+/// sent along if there's one.
+async fn ok(response: reqwest::Response) -> Result<reqwest::Response, Error> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<HashMap<String, String>>(&body)
+        .ok()
+        .and_then(|mut x| x.remove("message"))
+        .unwrap_or(body);
+    Err(Error::Api { status, message })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        },
+    };
+
+    /// Serves `[]` to every request, counting the connections it accepts.
+    async fn mock_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = Arc::clone(&connections);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    while let Ok(n @ 1..) = stream.read(&mut buf).await {
+                        request.extend_from_slice(&buf[..n]);
+                        // NOTE: The requests are GETs, so they end with their headers
+                        if request.ends_with(b"\r\n\r\n") {
+                            request.clear();
+                            let response = "HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\n[]";
+                            if stream.write_all(response.as_bytes()).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        (url, connections)
+    }
+
+    #[tokio::test]
+    async fn sequential_requests_reuse_the_connection() {
+        let (url, connections) = mock_server().await;
+        let client = build(&Config {
+            api_url: Some(url),
+            ..Config::default()
+        })
+        .unwrap();
+
+        for _ in 0..3 {
+            assert!(client.categories().await.unwrap().is_empty());
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,152 @@
+use {
+    crate::names,
+    aghpb::BookData,
+    color_eyre::{
+        eyre::{Context, ContextCompat},
+        Result, Section,
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        fs, io,
+        path::PathBuf,
+        sync::Arc,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// How many categories are remembered, the ones visited longest ago are
+/// forgotten first.
+const MAX_CATEGORIES: usize = 100;
+
+struct Visit {
+    // Seconds since the Unix epoch
+    timestamp: u64,
+    // Hashes of the book names in the category at the time
+    names: HashSet<u64>,
+}
+
+/// The books each category had when it was last visited, persisted across
+/// runs as one tab separated category, timestamp and comma separated list of
+/// name hashes per line.
+#[derive(Default)]
+pub struct KnownBooks {
+    visits: HashMap<String, Visit>,
+}
+
+impl KnownBooks {
+    pub fn load() -> Result<KnownBooks> {
+        let visits = match fs::read_to_string(path()?) {
+            Ok(contents) => contents
+                .lines()
+                .filter_map(|x| {
+                    let mut fields = x.split('\t');
+                    let category = fields.next()?.to_owned();
+                    let timestamp = fields.next()?.parse().ok()?;
+                    let names = fields
+                        .next()?
+                        .split(',')
+                        .filter_map(|x| u64::from_str_radix(x, 16).ok())
+                        .collect();
+                    Some((category, Visit { timestamp, names }))
+                })
+                .collect(),
+            // No category has been visited yet
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e).wrap_err("unable to read the known books file"),
+        };
+        Ok(KnownBooks { visits })
+    }
+
+    /// Remembers the books the category has now. Returns the identifiers of
+    /// the books that weren't there on the previous visit and how long ago
+    /// that was, or `None` on the first visit.
+    pub fn visit(
+        &mut self,
+        category: &str,
+        images: &[Arc<BookData>],
+    ) -> Result<Option<(HashSet<String>, Duration)>> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let names = images
+            .iter()
+            .map(|x| hash(&names::normalize(&x.name)))
+            .collect::<HashSet<_>>();
+
+        let new = self.visits.get(category).map(|visit| {
+            let new = images
+                .iter()
+                .filter(|x| !visit.names.contains(&hash(&names::normalize(&x.name))))
+                .map(|x| x.search_id.clone())
+                .collect();
+            (
+                new,
+                Duration::from_secs(now.saturating_sub(visit.timestamp)),
+            )
+        });
+
+        self.visits.insert(
+            category.to_owned(),
+            Visit {
+                timestamp: now,
+                names,
+            },
+        );
+        if self.visits.len() > MAX_CATEGORIES {
+            // Impossible for this to explode as the map isn't empty
+            let oldest = self
+                .visits
+                .iter()
+                .min_by_key(|(_, x)| x.timestamp)
+                .map(|(category, _)| category.clone())
+                .unwrap();
+            self.visits.remove(&oldest);
+        }
+        self.save()?;
+
+        Ok(new)
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = path()?;
+        // Impossible for the path to not have a parent as it's built from the data
+        // directory
+        fs::create_dir_all(path.parent().unwrap())
+            .wrap_err("unable to create the data directory")?;
+
+        let mut lines = self
+            .visits
+            .iter()
+            .map(|(category, visit)| {
+                let names = visit
+                    .names
+                    .iter()
+                    .map(|x| format!("{x:x}"))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!("{category}\t{}\t{names}", visit.timestamp)
+            })
+            .collect::<Vec<_>>();
+        // Keep the file stable between writes
+        lines.sort_unstable();
+        fs::write(path, lines.join("\n"))
+            .wrap_err("unable to write the known books file")
+            .suggestion("verify the permissions of your data directory")
+    }
+}
+
+/// FNV-1a, as the hashes are written to disk they have to stay the same
+/// between builds, which the standard library's hasher doesn't promise.
+fn hash(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, x| {
+        (hash ^ u64::from(x)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn path() -> Result<PathBuf> {
+    let mut path = dirs::data_dir().wrap_err("unable to locate data directory")?;
+    path.push("aghpb-tui");
+    path.push("known_books");
+    Ok(path)
+}
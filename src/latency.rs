@@ -0,0 +1,120 @@
+use {
+    color_eyre::{
+        eyre::{Context, ContextCompat},
+        Result,
+    },
+    serde_json::json,
+    std::{
+        collections::VecDeque,
+        fs::{self, File},
+        future::Future,
+        io::Write,
+        path::PathBuf,
+        sync::{Arc, Mutex, PoisonError},
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// How many of the latest samples the averages are taken over.
+const WINDOW: usize = 20;
+
+/// What a sample timed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    // A request to the API
+    Api,
+    // Decoding an image and building the protocol to show it with
+    Render,
+}
+
+#[derive(Default)]
+struct Samples {
+    api: VecDeque<Duration>,
+    render: VecDeque<Duration>,
+    // Every sample is written here as a line of JSON with `--verbose`
+    log: Option<File>,
+}
+
+/// Rolling averages of how long the API calls and image encodes take, shared
+/// with the tasks doing them.
+#[derive(Clone, Default)]
+pub struct Latency(Arc<Mutex<Samples>>);
+
+impl Latency {
+    /// Same as [`Latency::default`], but also logs every sample to the file
+    /// at [`log_path`].
+    pub fn with_log() -> Result<Latency> {
+        let path = log_path()?;
+        // Impossible for the path to not have a parent as it's built from the cache
+        // directory
+        fs::create_dir_all(path.parent().unwrap())
+            .wrap_err("unable to create the cache directory")?;
+        let log = File::options()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .wrap_err_with(|| format!("unable to open the log file: `{}`", path.display()))?;
+        Ok(Latency(Arc::new(Mutex::new(Samples {
+            log: Some(log),
+            ..Default::default()
+        }))))
+    }
+
+    pub fn record(&self, kind: Kind, what: &str, duration: Duration) {
+        let mut samples = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(log) = &mut samples.log {
+            let line = json!({
+                "timestamp": SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                "kind": match kind {
+                    Kind::Api => "api",
+                    Kind::Render => "render",
+                },
+                "what": what,
+                "ms": duration.as_millis(),
+            });
+            // NOTE: Losing a log line isn't worth interrupting the user over
+            let _ = writeln!(log, "{line}");
+        }
+
+        let samples = match kind {
+            Kind::Api => &mut samples.api,
+            Kind::Render => &mut samples.render,
+        };
+        if samples.len() == WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(duration);
+    }
+
+    /// Awaits the future and records how long it took.
+    pub async fn time<T>(&self, kind: Kind, what: &str, future: impl Future<Output = T>) -> T {
+        let start = Instant::now();
+        let output = future.await;
+        self.record(kind, what, start.elapsed());
+        output
+    }
+
+    /// Returns the average duration of the latest samples of the kind, `None`
+    /// if nothing was timed yet.
+    pub fn average(&self, kind: Kind) -> Option<Duration> {
+        let samples = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let samples = match kind {
+            Kind::Api => &samples.api,
+            Kind::Render => &samples.render,
+        };
+        // Impossible for this to truncate as the window is tiny
+        (!samples.is_empty()).then(|| samples.iter().sum::<Duration>() / samples.len() as u32)
+    }
+}
+
+/// Where `--verbose` logs the samples.
+fn log_path() -> Result<PathBuf> {
+    let mut path = dirs::cache_dir().wrap_err("unable to locate cache directory")?;
+    path.push("aghpb-tui");
+    path.push("latency.log");
+    Ok(path)
+}
@@ -8,6 +8,12 @@ pub fn centered_rect(area: Rect, horizontal: Constraint, vertical: Constraint) -
     area
 }
 
+/// Returns the area of an overlay `height` cells tall, floating in the middle
+/// of `area`.
+pub fn floating_overlay(area: Rect, width: Constraint, height: u16) -> Rect {
+    centered_rect(area, width, Constraint::Length(height))
+}
+
 pub fn centered_text<const S: usize>(text: [&str; S], height: u16) -> String {
     let num_of_lines = text.len();
     let text = text.join("\n");
@@ -15,7 +21,10 @@ pub fn centered_text<const S: usize>(text: [&str; S], height: u16) -> String {
         "{}{text}",
         // HACK: Get the text centered vertically
         "\n".repeat(
-            (f32::from(height) / 2f32) as usize - usize::from(height % 2 == 0) - num_of_lines
+            // NOTE: Saturating, as the window can be shorter than the text
+            ((f32::from(height) / 2f32) as usize)
+                .saturating_sub(usize::from(height.is_multiple_of(2)))
+                .saturating_sub(num_of_lines)
         ),
     )
 }
@@ -0,0 +1,98 @@
+use {
+    crate::{http, names},
+    aghpb::BookData,
+    color_eyre::{eyre::Context, Result, Section},
+    std::sync::Arc,
+};
+
+/// Where an image list comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListSource {
+    Category(String),
+    // The query the list was searched for, and the category the search was
+    // limited to
+    Search(String, Option<String>),
+    // The tag and the categories carrying it
+    Tag(String, Vec<String>),
+}
+
+impl ListSource {
+    pub fn title(&self) -> String {
+        match self {
+            ListSource::Category(category) => format!(" {category} "),
+            ListSource::Search(query, None) if query.trim().is_empty() => " All images ".to_owned(),
+            ListSource::Search(query, Some(category)) if query.trim().is_empty() => {
+                format!(" All images in {category} ")
+            },
+            ListSource::Search(query, None) => format!(" Results for \"{query}\" "),
+            ListSource::Search(query, Some(category)) => {
+                format!(" Results for \"{query}\" in {category} ")
+            },
+            ListSource::Tag(tag, _) => format!(" Tagged {tag} "),
+        }
+    }
+
+    /// Whether the list is every image rather than the results of a search,
+    /// from searching with an empty query.
+    pub fn is_browse_all(&self) -> bool {
+        matches!(self, ListSource::Search(query, _) if query.trim().is_empty())
+    }
+
+    /// Fetches the image list from the API.
+    pub async fn fetch(&self) -> Result<Vec<Arc<BookData>>> {
+        let images = match self {
+            ListSource::Category(category) => {
+                // NOTE: Searching with " " as the query gives us all of the images (as every
+                // image contains at least one " " in its title)
+                let mut images = http::client()
+                    .search(" ", Some(category), None)
+                    .await
+                    .wrap_err_with(|| {
+                        format!("unable to retrieve image list of category: `{category}`")
+                    })
+                    .suggestion("check your internet connectivity")?;
+
+                // PERF: Normalizing is expensive enough to warrant `cached_key`
+                images.sort_by_cached_key(|x| names::normalize(&x.name));
+                images
+            },
+            // NOTE: The API treats a blank query as matching everything
+            ListSource::Search(_, category) if self.is_browse_all() => {
+                let mut images = http::client()
+                    .search(" ", category.as_deref(), None)
+                    .await
+                    .wrap_err("unable to retrieve the list of every image")
+                    .suggestion("check your internet connectivity")?;
+
+                images.sort_by_cached_key(|x| names::normalize(&x.name));
+                images
+            },
+            // NOTE: We're not sorting this as the API returns the list already sorted with
+            // the best matching results first.
+            ListSource::Search(query, category) => http::client()
+                .search(query, category.as_deref(), None)
+                .await
+                .wrap_err_with(|| format!("unable to search using the query: {query}"))
+                .suggestion("check your internet connectivity")?,
+            ListSource::Tag(_, categories) => {
+                let mut images = Vec::new();
+                for category in categories {
+                    images.extend(
+                        http::client()
+                            .search(" ", Some(category), None)
+                            .await
+                            .wrap_err_with(|| {
+                                format!("unable to retrieve image list of category: `{category}`")
+                            })
+                            .suggestion("check your internet connectivity")?,
+                    );
+                }
+
+                images.sort_by_cached_key(|x| names::normalize(&x.name));
+                images
+            },
+        };
+
+        Ok(images.into_iter().map(Arc::new).collect())
+    }
+}
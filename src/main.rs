@@ -4,14 +4,35 @@
 
 use {
     aghpb::BookData,
+    api::{ApiVersion, SUPPORTED_API_MAJOR},
+    background::Background,
+    base64::{engine::general_purpose::STANDARD, Engine},
+    blacklist::Blacklist,
+    book_link::BookLink,
     bytes::Bytes,
+    cache::CacheEntry,
+    clap::Parser,
+    cli::Cli,
     color_eyre::{
-        eyre::{eyre, Context, ContextCompat},
+        eyre::{Context, ContextCompat},
         Result, Section,
     },
-    layout::{centered_rect, centered_text},
+    config::{Config, ConfigError, ImageBackground},
+    download_queue::DownloadQueue,
+    favorites::{Favorite, Favorites},
+    futures_util::{future::Fuse, FutureExt, StreamExt},
+    image::{
+        io::{Limits, Reader as ImageReader},
+        DynamicImage, ImageError, ImageFormat, ImageResult,
+    },
+    known_books::KnownBooks,
+    latency::{Kind, Latency},
+    layout::{centered_rect, centered_text, floating_overlay},
+    list_source::ListSource,
+    native_size::NativeImage,
+    notes::Notes,
     ratatui::{
-        crossterm::event::{self, Event, KeyCode, KeyEvent},
+        crossterm::event::{self, Event, EventStream, KeyCode, KeyEvent, KeyModifiers},
         layout::Flex,
         prelude::*,
         widgets::*,
@@ -19,20 +40,120 @@ use {
     ratatui_image::{
         picker::{Picker, ProtocolType},
         protocol::StatefulProtocol,
-        StatefulImage,
+        FilterType, Resize, StatefulImage,
+    },
+    seen::Seen,
+    serde_json::json,
+    session::Session,
+    sort::SortBy,
+    stateful_list::{StatefulList, StatefulListState},
+    std::{
+        collections::{HashMap, HashSet, VecDeque},
+        io::{Cursor, Write},
+        panic,
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::{Duration, Instant},
     },
-    stateful_list::StatefulList,
-    std::{env, fs, sync::Arc, time::Duration},
-    tokio::task::JoinSet,
+    tempfile::TempPath,
+    toast::Toast,
+    tokio::{
+        fs::{self, File},
+        io::AsyncWriteExt,
+        sync::{oneshot, Semaphore},
+        task::JoinSet,
+    },
+    transition::Transition,
+    tui::SharedPicker,
     tui_input::{backend::crossterm::EventHandler, Input},
+    tui_textarea::TextArea,
+    widgets::Popup,
 };
 
 // TODO: Configure codespell
 
+/// How long the search input has to stay unchanged before suggestions are
+/// fetched for it.
+const SUGGESTIONS_DEBOUNCE: Duration = Duration::from_millis(300);
+/// Shortest time between two images replacing each other in the image pane.
+/// Images coming in faster while scrolling are skipped for the latest one, so
+/// the terminal isn't sent more than about 10 images a second.
+const MIN_IMAGE_INTERVAL: Duration = Duration::from_millis(100);
+/// How long the selection has to rest on a category before its image list is
+/// fetched ahead of time.
+const HOVER_PRELOAD_DELAY: Duration = Duration::from_millis(300);
+/// How many categories from the top of the list have their image lists
+/// fetched as soon as the categories are shown, until changed with `<` and
+/// `>`.
+const PREFETCHED_CATEGORIES: usize = 3;
+/// The most categories `>` prefetches, each one is a request and a list kept
+/// in memory.
+const MAX_PREFETCHED_CATEGORIES: usize = 10;
+/// What a decoded pixel takes up in memory, as RGBA with 8 bits per channel.
+/// Images are refused when decoding them would take more than
+/// `max_image_megapixels` worth of them.
+const DECODED_BYTES_PER_PIXEL: u64 = 4;
+/// The smallest window the app is drawn in, smaller ones only get a warning.
+const MIN_WINDOW_WIDTH: u16 = 72;
+const MIN_WINDOW_HEIGHT: u16 = 9;
+/// The most rows the instructions at the bottom take up, however narrow the
+/// window.
+const MAX_FOOTER_HEIGHT: u16 = 3;
+/// How long to wait for input when nothing is going on in the background.
+const IDLE_POLL_TIMEOUT: Duration = Duration::from_secs(2);
+/// How many messages are handled before the next frame is drawn, the rest wait
+/// for the following frame so a runaway message chain can't freeze the UI.
+const MAX_MESSAGES_PER_FRAME: usize = 100;
+/// Data URIs longer than this get a warning, as a lot of terminals and
+/// applications choke on them.
+const LARGE_DATA_URI_BYTES: usize = 1024 * 1024;
+/// Search results matching worse than this are set apart in the list.
+const WEAK_RELEVANCE: f32 = 0.5;
+/// How many suggestions are shown below the search input.
+const SUGGESTIONS_LIMIT: u8 = 5;
+/// Shown in the image pane while no image is, rendered like any other image so
+/// a broken graphics protocol shows up right at startup.
+const PLACEHOLDER_IMAGE: &[u8] = include_bytes!("../assets/placeholder.png");
+/// How long a reordered favorite stays highlighted.
+const MOVED_FAVORITE_HIGHLIGHT: Duration = Duration::from_millis(600);
+
+mod accent;
+mod api;
+mod background;
+mod benchmark;
+mod blacklist;
+mod book_link;
+mod cache;
+mod cli;
+mod clipboard;
+mod config;
+mod download_queue;
 mod errors;
+mod export;
+mod favorites;
+mod file_manager;
+mod fuzzy;
+mod help;
+mod http;
+mod known_books;
+mod latency;
 mod layout;
+mod list_source;
+mod names;
+mod native_size;
+mod notes;
+mod picker_cache;
+mod seen;
+mod session;
+mod signals;
+mod sort;
+mod spinner;
 mod stateful_list;
+mod tags;
+mod toast;
+mod transition;
 mod tui;
+mod widgets;
 
 #[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
 enum RunningState {
@@ -40,20 +161,97 @@ enum RunningState {
     Loading,
     BrowsingCategories,
     BrowsingImages,
+    // Tags split out of the category names, each spanning the categories carrying it
+    BrowsingByTag,
+    // A search or category came back without any images
+    EmptyResults,
     Searching,
     ShowingDownloadPopup,
+    ShowingDownloadQueue,
+    ShowingCategoryPalette,
+    ShowingHelp,
+    ShowingFavorites,
+    ShowingBlacklist,
+    FindingInList,
+    EditingNote,
+    // Two marked images side by side, at their real size
+    Comparing,
     Exit,
 }
 
+/// The pane the arrow keys act on, the other keys work the same in every pane.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+enum Focus {
+    #[default]
+    List,
+    Image,
+    // Only while an image is pinned
+    Pinned,
+}
+
+/// An action that can be taken back with Ctrl+Z.
+enum UndoableAction {
+    Favorited(Favorite),
+    Unfavorited(Favorite),
+    Downloaded {
+        path: PathBuf,
+        name: String,
+        category: String,
+    },
+}
+
+/// An image load that failed over a flaky connection and is being tried again.
+struct ImageRetry {
+    book: Arc<BookData>,
+    max_pixels: Option<u64>,
+    // How many times the image has been fetched again so far
+    attempt: u8,
+}
+
+/// Random books shown fullscreen over the app after it was left alone for a
+/// while. The app underneath is left as it was.
+struct Screensaver {
+    image: Option<Image>,
+    // When the next book is fetched, `None` while one is being fetched
+    next_at: Option<Instant>,
+}
+
+/// An image list put aside by loading another one, kept the way it was left
+/// so it can be switched back to.
+struct SavedList {
+    source: ListSource,
+    images: Vec<Arc<BookData>>,
+    state: StatefulListState,
+}
+
 struct Image {
+    // The book this image was loaded from, used to load it again. `None` for local
+    // files.
+    book: Option<Arc<BookData>>,
     name: String,
+    search_id: String,
+    category: String,
     // Stores the image widget state for rendering
     state: Box<dyn StatefulProtocol>,
     // Stores the raw bytes for download
     data: Bytes,
     protocol: ProtocolType,
+    // Size of a terminal cell in pixels
+    font_size: (u16, u16),
     height: u16,
     width: u16,
+    timings: Timings,
+}
+
+/// Where the time it took to show an image went, to tell whether slowness is
+/// the network or the CPU.
+#[derive(Default, Clone, Copy)]
+struct Timings {
+    // `None` for images that weren't fetched, like local files
+    fetch: Option<Duration>,
+    decode: Duration,
+    // How long the first draw took, `None` until then
+    render: Option<Duration>,
 }
 
 enum Message {
@@ -61,43 +259,380 @@ enum Message {
     LoadImage,
     BrowseCategories,
     Exit,
+    // Starts over from loading the categories, keeping the config and what's saved
+    Reset,
     MoveUpCategories,
     MoveDownCategories,
+    // Looks the image up by its name and opens it in its category
+    ShowImageByName(String),
+    // The book found for the name, `None` if there's none
+    DeepLinkResolved(String, Option<Arc<BookData>>),
+    // Fetches the image list of the category into the cache before it's opened
+    PreloadCategory(String),
+    // Prefetches one category more or less
+    ChangePrefetchedCategories(i8),
+    // `None` if the list couldn't be fetched, it's fetched again when opened
+    CategoryPreloaded(String, Option<Vec<Arc<BookData>>>),
+    // Selects the first category starting with the letter
+    JumpToLetter(char),
+    // Shows the tags, working them out of the categories if they changed
+    LoadTags,
+    MoveUpTags,
+    MoveDownTags,
     MoveUpImages,
     MoveDownImages,
     ShowImage(Image),
+    TogglePreview,
+    // Switches between fitting the image to the image pane and showing it at its
+    // real size
+    ToggleNativeSize,
+    NativeImageDecoded(Option<NativeImage>),
+    ToggleMark,
+    Compare,
+    CompareLoaded(Result<[NativeImage; 2], String>),
+    ZoomImage(i8),
+    SwapCompared,
+    CloseCompare,
+    // Pans the image shown at its real size in the direction of the signs
+    PanImage(i8, i8),
+    // The next image in the list, `None` if it couldn't be loaded
+    PreviewLoaded(Option<Image>),
+    // What fills the image pane around the image with the identifier, `None` if it
+    // couldn't be computed
+    BackgroundComputed(String, Option<Background>),
+    // The average color of the image with the identifier, used as the accent.
+    // `None` if it couldn't be computed.
+    AccentComputed(String, Option<Color>),
+    // Loads the selected image even if it's over the size limit
+    ForceLoadImage,
+    ReloadImage,
+    ImageDecodeFailed {
+        search_id: String,
+        reason: String,
+    },
+    // Fetching the image with the identifier failed, `transient` failures are worth
+    // trying again
+    ImageFetchFailed {
+        search_id: String,
+        reason: String,
+        transient: bool,
+    },
+    // Fetches the image with the identifier again once the retry delay is over
+    RetryImageLoad(String),
+    // The image with the identifier is over `confirm_above_bytes`, load it only once
+    // the user says so
+    ConfirmImageLoad {
+        search_id: String,
+        bytes: u64,
+    },
+    AcceptImageLoad,
+    SkipImageLoad,
     DownloadImage,
-    ShowImageList(String),
+    DownloadThumbnails,
+    TogglePin,
+    EditNote,
+    HandleNoteInput(KeyEvent),
+    SaveNote,
+    CancelNote,
+    PinImage(Image),
+    SwitchFocus,
+    ShowPreviousImage,
+    ShowNextImage,
+    CopyDataUri,
+    // Writes the image to a temp file and copies its path
+    CopyToTempFile,
+    DownloadFinished(usize, Result<(), String>),
+    ToggleDownloadQueue,
+    MoveUpDownloads,
+    MoveDownDownloads,
+    RetryDownload,
+    CancelDownload,
+    OpenCategoryPalette,
+    HandleCategoryPaletteInput(KeyEvent),
+    MoveUpCategoryPalette,
+    MoveDownCategoryPalette,
+    CloseCategoryPalette,
+    LoadList(ListSource),
+    // The categories fetched again in the background, `None` if that failed
+    CategoriesRefreshed(Option<Vec<String>>),
+    ListLoaded(ListSource, Vec<Arc<BookData>>),
+    // The list that couldn't be fetched, and why
+    ListLoadFailed(ListSource, String),
+    // Sorts the image list by the next criterion
+    CycleSort,
+    ResetView,
+    FocusChanged(bool),
+    // Switches between the last category list and the last search results
+    SwitchList,
+    // Shows the weak search results that were collapsed
+    ExpandResults,
+    AutoRefreshList,
+    ForceReloadImageList,
     DismissDownloadPrompt,
+    // The version the API reports, `None` if it couldn't be checked
+    ApiChecked(Option<ApiVersion>),
+    DismissApiWarning,
+    StartScreensaver,
+    NextScreensaverImage,
+    // The random book the screensaver shows next, `None` if it couldn't be loaded
+    ScreensaverImage(Option<Image>),
+    StopScreensaver,
+    // Copies what the app is doing as JSON, to reproduce bugs with
+    CopyState,
+    // Searches again after a search came back empty
+    GoBack,
+    // Opens the folder of the finished download in the file manager
+    OpenDownloadFolder,
+    CopyDownloadPath,
     Search,
     HandleSearchInput(KeyEvent),
+    FetchSuggestions,
+    // The query the suggestions were fetched for, and the suggestions themselves
+    ShowSuggestions(String, Vec<String>),
+    MoveUpSuggestions,
+    MoveDownSuggestions,
+    CompleteSuggestion,
     ShowSearchResults,
+    ClearSeen,
+    ToggleFavorite,
+    ExportBookmarks,
+    ExportList,
+    ToggleHelp,
+    MoveUpHelp,
+    MoveDownHelp,
+    ToggleFavorites,
+    Undo,
+    // Names of the books found in the download directory of each category
+    DownloadsScanned(Vec<(String, HashSet<String>)>),
+    // Selects the next or previous book that isn't in the download directory
+    NextNotDownloaded,
+    PreviousNotDownloaded,
+    // Switches the search between the category it was started from and every
+    // category
+    ToggleSearchScope,
+    OpenFind,
+    HandleFindInput(KeyEvent),
+    // Selects the next or previous item matching the find input
+    FindNext,
+    FindPrevious,
+    // Closes the find input, keeping the selection or going back to where it was
+    AcceptFind,
+    CancelFind,
+    MoveUpFavorites,
+    MoveDownFavorites,
+    // Swaps the selected favorite with its neighbor
+    MoveFavoriteUp,
+    MoveFavoriteDown,
+    // Leaves the selected image out of every list from now on
+    HideImage,
+    ToggleBlacklist,
+    MoveUpBlacklist,
+    MoveDownBlacklist,
+    UnhideImage,
 }
 
 #[derive(Default)]
+#[allow(clippy::struct_excessive_bools)]
 struct App {
+    config: Config,
     running_state: RunningState,
-    // Used to return to the previous running state after download popup dismissal.
+    // Used to return to the previous running state after download popup, download
+    // queue or category palette dismissal.
     previous_running_state: RunningState,
     categories: StatefulList,
+    tags: Vec<String>,
+    // The tags with the number of categories carrying them
+    tags_list: StatefulList,
+    // How old the categories are when they were loaded from the cache instead of
+    // the API
+    category_cache_age: Option<Duration>,
     image: Option<Image>,
+    // Kept around while another image loads, so it can be shown again if that one
+    // fails to decode
+    previous_image: Option<Image>,
+    // Shown next to the current image to compare the two
+    pinned: Option<Image>,
+    // Downloading and copying act on the pinned image while it has focus, and the
+    // current one otherwise
+    focus: Focus,
+    placeholder: Option<Image>,
+    // Whether the next image in the list is previewed in a corner of the image
+    // pane
+    show_preview: bool,
+    // The next image in the list, it's only shown while it's still the next one
+    preview: Option<Image>,
+    // Accent color picked from the image with the identifier, if dynamic accents
+    // are on
+    accent: Option<(String, Color)>,
+    // Whether images are shown at their real size instead of fit to the pane
+    native_size: bool,
+    // The current image decoded for showing it at its real size
+    native_image: Option<NativeImage>,
+    // The list pane sliding between the categories and the images
+    transition: Option<Transition>,
+    // Whether the terminal lost focus, and the animations are paused for it
+    paused: bool,
+    // When the shown image was last replaced
+    image_shown_at: Option<Instant>,
+    // The latest image that came in too soon after the last one, shown once
+    // `MIN_IMAGE_INTERVAL` is over
+    pending_image: Option<Image>,
+    // Bytes sent to the terminal in the last second, and when and at which
+    // total the second started
+    sent_per_sec: u64,
+    sent_window: Option<(Instant, u64)>,
+    // Images marked to be compared, at most two and in the order they're shown
+    marked: Vec<Arc<BookData>>,
+    // The marked images decoded for comparing, `None` while they load
+    compared: Option<[NativeImage; 2]>,
+    // Fill of the image pane around the image with the identifier
+    background: Option<(String, Background)>,
+    // Where the image pane's contents were last drawn
+    image_area: Rect,
     images: Vec<Arc<BookData>>,
+    // The name of the image to open once the categories load, from `--open`
+    deep_link_target: Option<String>,
+    // The image selected in each category when it was left, selected again when
+    // it's opened
+    category_selections: HashMap<String, String>,
+    // `images` in the order the list came in, to sort it differently
+    source_images: Vec<Arc<BookData>>,
+    sort_by: SortBy,
     images_list: StatefulList,
-    shown_at_least_one_image: bool,
+    // How well each search result matches the query, empty for other lists
+    relevance: Vec<f32>,
+    // How many weak search results are collapsed at the end of `images`, they're
+    // shown as a single row in the list
+    collapsed: usize,
+    // Where the shown image list came from, `None` before any list was shown
+    list_source: Option<ListSource>,
+    // The last category and search result lists that were left, switched between
+    // with `L`
+    last_category_list: Option<SavedList>,
+    last_search_list: Option<SavedList>,
+    // The image list being fetched, lists that arrive for anything else are stale
+    loading_list: Option<ListSource>,
+    // When to fetch the shown image list again, if auto refresh is on
+    auto_refresh_deadline: Option<Instant>,
+    // Whether the image list being fetched is an auto refresh
+    auto_refreshing: bool,
+    // Sorted image lists of the categories visited so far
+    category_cache: HashMap<String, CacheEntry<Vec<Arc<BookData>>>>,
+    // Identifiers of the images that have already been viewed
+    seen: Seen,
+    favorites: Favorites,
+    blacklist: Blacklist,
+    notes: Notes,
+    // Editor for the note about the current image
+    note_editor: TextArea<'static>,
+    known_books: KnownBooks,
+    // The previous run's session, each part is taken once it's been restored
+    session: Session,
+    // Identifiers of the books added upstream since their category was last
+    // visited
+    new_books: HashSet<String>,
+    // Decode failure reasons keyed by the identifier of the image that failed
+    failed_images: HashMap<String, String>,
+    // Whether an image is being fetched and decoded
+    loading_image: bool,
+    // Whether the image being loaded is the current image being fetched again
+    reloading_image: bool,
+    // Probed once at startup, building a picker is slow
+    picker: SharedPicker,
+    latency: Latency,
+    // Set while an image is being loaded, counts the retries of a flaky load
+    image_retry: Option<ImageRetry>,
+    // Size of the image being loaded while waiting for the user to confirm loading
+    // it
+    confirming_image: Option<u64>,
+    // The API version found at startup, if it's newer than the app knows, until
+    // the warning is dismissed
+    api_warning: Option<ApiVersion>,
+    // When a key was last pressed, to start the screensaver after
+    last_input: Option<Instant>,
+    screensaver: Option<Screensaver>,
     search_input: Input,
+    // The category the search was started from, searches can be limited to it
+    search_category: Option<String>,
+    // Autocomplete suggestions for the search input, nothing is selected while the
+    // input itself has focus
+    suggestions: StatefulList,
+    // When to fetch suggestions for the current search input
+    suggestions_deadline: Option<Instant>,
+    // The category the selection rests on and since when, to preload it
+    hover_timer: Option<(String, Instant)>,
+    // Categories whose image lists are being preloaded
+    preloading: HashSet<String>,
+    // How many categories from the top of the list are preloaded
+    prefetched_categories: usize,
+    // Categories preloaded into `category_cache` that weren't opened since
+    preloaded: HashSet<String>,
+    downloads: DownloadQueue,
+    // Name and category of every book downloaded this session
+    downloaded: HashSet<(String, String)>,
+    // Path and size in bytes of the download the popup is about
+    finished_download: Option<(PathBuf, usize)>,
+    // When the download popup and the API warning opened, to close each after
+    // `popup_timeout_secs`
+    download_popup_opened_at: Option<Instant>,
+    api_warning_opened_at: Option<Instant>,
+    // Names of the books in the download directory, keyed by category. Scanned
+    // once per category and kept up to date as downloads finish.
+    on_disk: HashMap<String, HashSet<String>>,
+    // Images written out with Y, removed once they're dropped when the app quits
+    temp_files: Vec<TempPath>,
+    // Identifier of the image the user was warned is already downloaded, pressing
+    // download again overwrites it
+    overwrite_pending: Option<String>,
+    category_palette_input: Input,
+    // Text to find in the browsed list
+    find_input: Input,
+    // What was selected in the browsed list before finding in it
+    find_origin: Option<usize>,
+    // Categories matching the category palette input, best matches first
+    category_palette: StatefulList,
+    help_state: TableState,
+    // Only the last action can be undone
+    last_undoable_action: Option<UndoableAction>,
+    // Names of the favorites, in their order, while they're shown
+    favorites_list: StatefulList,
+    // Names of the hidden images, in their order, while they're shown
+    blacklist_list: StatefulList,
+    // When the selected favorite was last reordered
+    favorite_moved_at: Option<Instant>,
+    toast: Option<Toast>,
     tasks: JoinSet<Result<Message>>,
 }
 
-#[allow(clippy::too_many_lines)]
 fn view(app: &mut App, f: &mut Frame) {
     let window_size = f.size();
-    if window_size.height <= 8 || window_size.width <= 72 {
+    let too_small = window_size.height < MIN_WINDOW_HEIGHT || window_size.width < MIN_WINDOW_WIDTH;
+    // The lists scroll to keep their selection in the tiny window, their offsets
+    // are put back so they're where they were once it's large enough again
+    let offsets = too_small.then(|| {
+        (
+            app.list_states().map(|x| x.offset()),
+            app.help_state.offset(),
+        )
+    });
+    view_app(app, f);
+    if let Some((lists, help)) = offsets {
+        for (state, offset) in app.list_states().into_iter().zip(lists) {
+            *state.offset_mut() = offset;
+        }
+        *app.help_state.offset_mut() = help;
+    }
+
+    // NOTE: Drawn over the app instead of in its place, so the image keeps
+    // following the window and is fit to it again once it's large enough
+    if too_small {
         let msg = format!(
             "Window dimensions are too low: {}x{}",
             window_size.height, window_size.width,
         );
         let msg_len = msg.len();
         let text = Paragraph::new(msg).on_red().centered();
+        f.render_widget(Clear, window_size);
         f.render_widget(
             text,
             centered_rect(
@@ -106,11 +641,18 @@ fn view(app: &mut App, f: &mut Frame) {
                 Constraint::Length(1),
             ),
         );
+    }
+}
+
+fn view_app(app: &mut App, f: &mut Frame) {
+    let window_size = f.size();
+    if let Some(screensaver) = &mut app.screensaver {
+        view_screensaver(screensaver, &app.config, f);
         return;
     }
 
     // Reused stuff
-    let thick_block = Block::bordered().border_type(BorderType::Thick);
+    let main_block = Block::bordered().border_type(app.config.border_style.main_blocks.into());
 
     match app.running_state {
         RunningState::Loading => {
@@ -119,383 +661,4987 @@ fn view(app: &mut App, f: &mut Frame) {
                 Constraint::Percentage(25),
                 Constraint::Length(3),
             );
-            let text = Paragraph::new(centered_text(["Loading..."], centered_rect.height))
-                .block(thick_block)
+            let loading = app
+                .config
+                .spinner
+                .with_text(app.config.tick_ms, &app.config.loading_text);
+            let text = Paragraph::new(centered_text([loading.as_str()], centered_rect.height))
+                .block(main_block)
                 .centered();
             f.render_widget(text, centered_rect);
         },
         RunningState::Searching => {
-            let layout = centered_rect(
-                window_size,
-                Constraint::Percentage(35),
-                Constraint::Length(5),
+            view_browsing(app, f);
+            view_searching(app, f);
+        },
+        RunningState::Comparing => view_compare(app, f),
+        _ => view_browsing(app, f),
+    }
+
+    // NOTE: Shown over whatever the app started into, as it's found before the
+    // categories load
+    if let Some(popup) = app.api_warning_popup() {
+        let area = popup.area(window_size);
+        f.render_widget(popup, area);
+    }
+
+    if let Some(toast) = &app.toast {
+        let text = Line::from(toast.message.as_str());
+        // `+ 4` to account for the block border and padding
+        let width = (text.width() as u16 + 4).min(window_size.width);
+        // Sit in the bottom right corner, just above the instructions
+        let area = Rect::new(
+            window_size.width - width,
+            window_size.height.saturating_sub(5),
+            width,
+            3,
+        );
+
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(text)
+                .block(Block::bordered().border_type(BorderType::Rounded))
+                .centered(),
+            area,
+        );
+    }
+}
+
+/// Fills the window with the screensaver's book, named at the bottom.
+fn view_screensaver(screensaver: &mut Screensaver, config: &Config, f: &mut Frame) {
+    let [image_area, caption_area] =
+        Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(f.size());
+
+    let Some(image) = &mut screensaver.image else {
+        let loading = config
+            .spinner
+            .with_text(config.tick_ms, &config.loading_text);
+        f.render_widget(
+            Paragraph::new(centered_text([loading.as_str()], image_area.height)).centered(),
+            image_area,
+        );
+        return;
+    };
+    render_image(f, image, image_area, config.cell_aspect_correction());
+    f.render_widget(
+        Line::from(vec![
+            image.name.as_str().bold(),
+            " · press any key to return".dim(),
+        ])
+        .centered(),
+        caption_area,
+    );
+}
+
+/// Fills the window with the two marked images side by side, named above
+/// them.
+fn view_compare(app: &mut App, f: &mut Frame) {
+    let [panes_area, hint_area] =
+        Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(f.size());
+    let panes: [Rect; 2] = Layout::horizontal([Constraint::Fill(1); 2]).areas(panes_area);
+
+    for (i, pane) in panes.into_iter().enumerate() {
+        let name = app.marked.get(i).map_or("", |x| x.name.as_str());
+        let block = Block::bordered()
+            .border_type(app.config.border_style.main_blocks.into())
+            .title(format!(" {} {name} ", ["①", "②"][i]));
+        let area = block.inner(pane);
+        f.render_widget(block, pane);
+
+        if let Some(images) = &mut app.compared {
+            images[i].render(f, area);
+        } else {
+            let loading = app
+                .config
+                .spinner
+                .with_text(app.config.tick_ms, &app.config.loading_text);
+            f.render_widget(
+                Paragraph::new(centered_text([loading.as_str()], area.height)).centered(),
+                area,
             );
+        }
+    }
 
-            let input = Paragraph::new(app.search_input.value())
-                .block(Block::default().borders(Borders::ALL).title(" Search "))
-                .scroll((
-                    0,
-                    app.search_input.visual_scroll(layout.width as usize - 4) as u16,
-                ));
-            f.render_widget(input, thick_block.inner(layout));
-        },
-        _ => {
-            let main_layout = Layout::vertical([Constraint::Percentage(95), Constraint::Length(2)])
-                .split(window_size);
+    f.render_widget(
+        Line::from("h / j / k / l pan · + / - zoom · s swap · Esc close".dim()).centered(),
+        hint_area,
+    );
+}
+
+/// Returns how many rows the instructions wrap to at `width`, at least the two
+/// lines they're made of and at most [`MAX_FOOTER_HEIGHT`].
+fn footer_height(lines: &[Line], width: u16) -> u16 {
+    let rows = lines
+        .iter()
+        .map(|x| {
+            let line = x.spans.iter().map(|x| &*x.content).collect::<String>();
+            textwrap::wrap(line.trim(), usize::from(width).max(1)).len() as u16
+        })
+        .sum::<u16>();
+    rows.clamp(2, MAX_FOOTER_HEIGHT)
+}
+
+/// Renders the category or image list next to the image pane, along with any
+/// popup open over them.
+#[allow(clippy::too_many_lines)]
+fn view_browsing(app: &mut App, f: &mut Frame) {
+    let window_size = f.size();
+    let main_block = Block::bordered().border_type(app.config.border_style.main_blocks.into());
+    let popup_block = Block::bordered().border_type(app.config.border_style.popup_blocks.into());
+
+    let mut primary_instructions = vec![
+        " Move Up ".into(),
+        "<Up>".green().bold(),
+        " Move Down ".into(),
+        "<Down>".green().bold(),
+    ];
+    let mut secondary_instructions = vec![];
+
+    if app.running_state == RunningState::EmptyResults {
+        primary_instructions = if let Some(ListSource::Search(..)) = app.list_source {
+            vec![" Search again ".into(), "<Backspace>".green().bold()]
+        } else {
+            vec![]
+        };
+        secondary_instructions.extend([
+            " Back ".into(),
+            "<Left>".green().bold(),
+            " Quit ".into(),
+            "<q>".green().bold(),
+        ]);
+    } else if app.running_state == RunningState::ShowingDownloadQueue {
+        primary_instructions.extend([
+            " Retry ".into(),
+            "<r>".green().bold(),
+            " Cancel ".into(),
+            "<x>".green().bold(),
+        ]);
+        secondary_instructions.extend([" Close ".into(), "<C-q> <Esc>".green().bold()]);
+    } else if app.running_state == RunningState::ShowingCategoryPalette {
+        primary_instructions.extend([" Jump ".into(), "<Enter>".green().bold()]);
+        secondary_instructions.extend([" Cancel ".into(), "<Esc>".green().bold()]);
+    } else if app.running_state == RunningState::Searching {
+        primary_instructions = vec![
+            " Search ".into(),
+            "<Enter>".green().bold(),
+            " Suggestions ".into(),
+            "<Tab> <Up> <Down>".green().bold(),
+        ];
+    } else if app.running_state == RunningState::EditingNote {
+        primary_instructions = vec![" Save ".into(), "<C-s>".green().bold()];
+        secondary_instructions.extend([" Cancel ".into(), "<Esc>".green().bold()]);
+    } else if app.running_state == RunningState::FindingInList {
+        primary_instructions = vec![
+            " Next ".into(),
+            "<Down> <Tab>".green().bold(),
+            " Previous ".into(),
+            "<Up> <S-Tab>".green().bold(),
+        ];
+        secondary_instructions.extend([
+            " Done ".into(),
+            "<Enter>".green().bold(),
+            " Cancel ".into(),
+            "<Esc>".green().bold(),
+        ]);
+    } else if app.running_state == RunningState::ShowingFavorites {
+        primary_instructions.extend([" Reorder ".into(), "<S-Up> <S-Down>".green().bold()]);
+        secondary_instructions.extend([" Close ".into(), "<F> <Esc>".green().bold()]);
+    } else if app.running_state == RunningState::ShowingBlacklist {
+        primary_instructions.extend([" Un-hide ".into(), "<u> <Enter>".green().bold()]);
+        secondary_instructions.extend([" Close ".into(), "<B> <Esc>".green().bold()]);
+    } else if app.running_state == RunningState::ShowingHelp {
+        secondary_instructions.extend([" Close ".into(), "<?> <Esc>".green().bold()]);
+    } else {
+        if app.focus == Focus::List {
+            primary_instructions.extend([
+                " Back ".into(),
+                "<Left>".green().bold(),
+                " Enter ".into(),
+                "<Right> <Enter>".green().bold(),
+            ]);
+        } else if app.running_state == RunningState::BrowsingImages {
+            primary_instructions = vec![
+                " Previous ".into(),
+                "<Up> <Left>".green().bold(),
+                " Next ".into(),
+                "<Down> <Right>".green().bold(),
+            ];
+        } else {
+            primary_instructions.clear();
+        }
+        if app.image.is_some() || app.pinned.is_some() {
+            primary_instructions.extend([" Switch Pane ".into(), "<Tab>".green().bold()]);
+        }
+        secondary_instructions.extend([" Search ".into(), "<s> </>".green().bold()]);
+        if app.running_state == RunningState::BrowsingCategories {
+            secondary_instructions.extend([
+                format!(
+                    " Prefetch {}/{MAX_PREFETCHED_CATEGORIES} ",
+                    app.prefetched_categories
+                )
+                .into(),
+                "<<> <>>".green().bold(),
+            ]);
+        }
 
-            let mut secondary_instructions = vec![" Search ".into(), "<s> </>".green().bold()];
+        if app.image.is_some() {
+            secondary_instructions.extend([" Download ".into(), "<d>".green().bold()]);
+            if !app.downloaded.is_empty() {
+                secondary_instructions.push(format!(" ({})", app.downloaded.len()).into());
+            }
+        }
 
-            if app.image.is_some() {
-                secondary_instructions.extend([" Download ".into(), "<d>".green().bold()]);
+        if app.running_state == RunningState::BrowsingImages {
+            if app.image.as_ref().is_some_and(|x| x.book.is_some()) {
+                secondary_instructions.extend([" Reload ".into(), "<F5>".green().bold()]);
+            }
+            secondary_instructions.extend([
+                " Favorite ".into(),
+                "<f>".green().bold(),
+                " Clear Seen ".into(),
+                "<c>".green().bold(),
+            ]);
+            if app.list_source.is_some() {
+                secondary_instructions.extend([
+                    " Refresh ".into(),
+                    "<R>".green().bold(),
+                    " Export List ".into(),
+                    "<X>".green().bold(),
+                ]);
             }
+        }
 
-            secondary_instructions.extend([" Quit ".into(), "<q>".green().bold()]);
+        secondary_instructions.extend([
+            " Jump ".into(),
+            "<C-p>".green().bold(),
+            " Downloads ".into(),
+            "<C-q>".green().bold(),
+            " Help ".into(),
+            "<?>".green().bold(),
+            " Quit ".into(),
+            "<q>".green().bold(),
+        ]);
 
-            let instructions = Paragraph::new(vec![
-                Line::from(vec![
-                    " Move Up ".into(),
-                    "<Up>".green().bold(),
-                    " Move Down ".into(),
-                    "<Down>".green().bold(),
-                    " Back ".into(),
-                    "<Left>".green().bold(),
-                    " Enter ".into(),
-                    "<Right> <Enter>".green().bold(),
-                ]),
-                Line::from(secondary_instructions),
-            ])
-            .wrap(Wrap { trim: true })
-            .centered();
+        if !app.downloads.is_empty() {
+            let (active, failed, done) = app.downloads.summary();
+            let summary = [(active, "active"), (failed, "failed"), (done, "done")]
+                .into_iter()
+                .filter(|(count, _)| *count > 0)
+                .map(|(count, label)| format!("{count} {label}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            secondary_instructions.push(format!(" ⬇ {summary}").yellow());
+        }
+        if app.config.show_latency {
+            let format =
+                |x: Option<Duration>| x.map_or("-".to_owned(), |x| format!("{}ms", x.as_millis()));
+            secondary_instructions.push(
+                format!(
+                    " API {} · render {} · sent {}/s",
+                    format(app.latency.average(Kind::Api)),
+                    format(app.latency.average(Kind::Render)),
+                    format_size(app.sent_per_sec as usize),
+                )
+                .dim(),
+            );
+            if let Some(Timings {
+                fetch,
+                decode,
+                render,
+            }) = app.image.as_ref().map(|x| x.timings)
+            {
+                secondary_instructions.push(
+                    format!(
+                        " · image: fetch {}, decode {}, render {}",
+                        format(fetch),
+                        format(Some(decode)),
+                        format(render),
+                    )
+                    .dim(),
+                );
+            }
+        }
+    }
 
-            f.render_widget(instructions, main_layout[1]);
+    let instructions = vec![
+        Line::from(primary_instructions),
+        Line::from(secondary_instructions),
+    ];
+    // NOTE: Narrower windows wrap the instructions, the footer grows to keep the
+    // last ones, like Quit, from being cut off
+    let main_layout = Layout::vertical([
+        Constraint::Percentage(95),
+        Constraint::Length(footer_height(&instructions, window_size.width)),
+    ])
+    .split(window_size);
+    let instructions = Paragraph::new(instructions)
+        .wrap(Wrap { trim: true })
+        .centered();
 
-            let app_layout =
-                Layout::horizontal([Constraint::Percentage(30), Constraint::Percentage(70)])
-                    .split(main_layout[0]);
+    f.render_widget(instructions, main_layout[1]);
 
-            let highlight_style = Style::default().bold().reversed().green();
+    let list_width = app.config.list_width_percent;
+    let app_layout = Layout::horizontal([
+        Constraint::Percentage(list_width),
+        Constraint::Percentage(100 - list_width),
+    ])
+    .split(main_layout[0]);
 
-            if let RunningState::BrowsingCategories = app.running_state {
-                let list = app
-                    .categories
-                    .get_list(app_layout[0].width)
-                    .block(thick_block.clone().title(" Select Language "))
-                    .highlight_style(highlight_style);
-                f.render_stateful_widget(list, app_layout[0], &mut app.categories.state);
-            } else {
-                let list = app
-                    .images_list
-                    .get_list(app_layout[0].width)
-                    .block(thick_block.clone().title(" Select Image "))
-                    .highlight_style(highlight_style);
-                f.render_stateful_widget(list, app_layout[0], &mut app.images_list.state);
-            }
-
-            let stateful_image = StatefulImage::new(None);
-
-            let image_block = thick_block.clone().title(" Image ");
-
-            if let Some(image) = &mut app.image {
-                let area = image_block.inner(app_layout[1]);
-                let protocol = image.protocol;
-
-                // HACK: Halfblocks doesn't work with fractional scailing
-                let image_layout = if image.width > image.height {
-                    let height = if protocol == ProtocolType::Halfblocks {
-                        (f32::from(area.width) * (f32::from(image.height) / f32::from(image.width))
-                            / 2.0)
-                            .ceil() as u16
-                    } else {
-                        (f32::from(area.width) * (f32::from(image.height) / f32::from(image.width))
-                            / 2.15)
-                            .floor() as u16
-                    };
-
-                    Layout::vertical([Constraint::Length(height)])
-                        .flex(Flex::Center)
-                        .split(area)
+    // The accent picked from the shown image, if it's still the one shown
+    let accent = app
+        .accent
+        .as_ref()
+        .filter(|(search_id, _)| {
+            app.image
+                .as_ref()
+                .is_some_and(|x| &x.search_id == search_id)
+        })
+        .map(|(_, color)| *color);
+    let accent = accent.unwrap_or(Color::Green);
+    let highlight_style = Style::default().bold().reversed().fg(accent);
+    // The border of the pane the arrow keys act on
+    let pane_block = |focus| {
+        if app.focus == focus {
+            Block::bordered()
+                .border_type(app.config.border_style.focused_block.into())
+                .border_style(Style::default().fg(accent))
+        } else {
+            main_block.clone()
+        }
+    };
+
+    // Popups are drawn over the list that was being browsed when they opened
+    let browsing_state = match app.running_state {
+        RunningState::ShowingDownloadPopup
+        | RunningState::ShowingDownloadQueue
+        | RunningState::ShowingCategoryPalette
+        | RunningState::ShowingHelp
+        | RunningState::ShowingFavorites
+        | RunningState::ShowingBlacklist
+        | RunningState::FindingInList
+        | RunningState::EditingNote
+        | RunningState::Searching => app.previous_running_state,
+        state => state,
+    };
+
+    if let RunningState::BrowsingCategories = browsing_state {
+        let block = pane_block(Focus::List).title(categories_title(app.category_cache_age));
+        // The alphabet bar takes up the first row inside the block
+        let [bar_area, list_area] = Layout::vertical([Constraint::Length(1), Constraint::Fill(1)])
+            .areas(block.inner(app_layout[0]));
+        f.render_widget(block, app_layout[0]);
+        f.render_widget(alphabet_bar(&app.categories, accent), bar_area);
+
+        let list = app
+            .categories
+            .get_selection_list(app_layout[0].width, app.config.list_overflow, |_| {
+                ("", Style::default())
+            })
+            .highlight_style(highlight_style);
+        f.render_stateful_widget(list, list_area, &mut app.categories.state);
+    } else if browsing_state == RunningState::BrowsingByTag {
+        let list = app
+            .tags_list
+            .get_list(app_layout[0].width, app.config.list_overflow)
+            .block(pane_block(Focus::List).title(" Select Tag "))
+            .highlight_style(highlight_style);
+        f.render_stateful_widget(list, app_layout[0], &mut app.tags_list.state);
+    } else if browsing_state == RunningState::EmptyResults {
+        let text = match &app.list_source {
+            Some(ListSource::Search(query, _)) => format!("No results for '{query}'"),
+            _ => "No images in this category".to_owned(),
+        };
+        let block = main_block.clone().title(images_title(
+            app.list_source.as_ref(),
+            app.loading_list.as_ref(),
+            app.sort_by,
+            app.config.spinner.frame(app.config.tick_ms),
+        ));
+        let area = block.inner(app_layout[0]);
+        let [text_area] = Layout::vertical([Constraint::Length(
+            textwrap::wrap(&text, usize::from(area.width).max(1)).len() as u16,
+        )])
+        .flex(Flex::Center)
+        .areas(area);
+        f.render_widget(block, app_layout[0]);
+        f.render_widget(
+            Paragraph::new(text).wrap(Wrap { trim: true }).centered(),
+            text_area,
+        );
+    } else {
+        let list = app
+            .images_list
+            .get_selection_list(app_layout[0].width, app.config.list_overflow, |i| {
+                // The row standing in for the collapsed results
+                if i >= app.visible_images() {
+                    return ("", Style::default().dim().italic());
+                }
+                let search_id = &app.images[i].search_id;
+                let slot = app.marked.iter().position(|x| x.search_id == *search_id);
+                let marker = if let Some(i) = slot {
+                    ["① ", "② "][i]
+                } else if app.failed_images.contains_key(search_id) {
+                    "✗ "
+                } else if app.favorites.contains(search_id) {
+                    "★ "
+                } else if app.is_on_disk(&app.images[i]) {
+                    "✓ "
+                } else if app.new_books.contains(search_id) {
+                    "NEW "
+                } else if app.notes.contains(&names::normalize(&app.images[i].name)) {
+                    "📝 "
                 } else {
-                    let width = if protocol == ProtocolType::Halfblocks {
-                        (f32::from(area.height)
-                            * (f32::from(image.width) / f32::from(image.height))
-                            * 2.0)
-                            .ceil() as u16
-                    } else {
-                        (f32::from(area.height)
-                            * (f32::from(image.width) / f32::from(image.height))
-                            * 2.15)
-                            .ceil() as u16
-                    };
-
-                    Layout::horizontal([Constraint::Length(width)])
-                        .flex(Flex::Center)
-                        .split(area)
+                    ""
                 };
+                let style = if app.failed_images.contains_key(search_id) {
+                    Style::default().red()
+                } else if app.seen.contains(search_id) {
+                    Style::default().dim()
+                } else if app.relevance.get(i).is_some_and(|x| *x < WEAK_RELEVANCE) {
+                    Style::default().italic()
+                } else {
+                    Style::default()
+                };
+                (marker, style)
+            })
+            .block(pane_block(Focus::List).title(images_title(
+                app.list_source.as_ref(),
+                app.loading_list.as_ref(),
+                app.sort_by,
+                app.config.spinner.frame(app.config.tick_ms),
+            )))
+            .highlight_style(highlight_style);
+        f.render_stateful_widget(list, app_layout[0], &mut app.images_list.state);
+    }
 
-                f.render_stateful_widget(stateful_image, image_layout[0], &mut image.state);
-            } else if app.shown_at_least_one_image {
-                let text = Paragraph::new(Text::from("Loading...")).centered();
-                f.render_widget(
-                    text,
-                    centered_rect(
-                        image_block.inner(app_layout[1]),
-                        Constraint::Percentage(35),
-                        Constraint::Length(1),
-                    ),
-                );
-            }
-            f.render_widget(image_block, app_layout[1]);
+    // NOTE: Going back before the slide is over leaves nothing to slide
+    if app
+        .transition
+        .is_some_and(|x| x.from_state == app.running_state)
+    {
+        app.transition = None;
+    }
+    if let Some(transition) = &mut app.transition {
+        if transition.apply(app_layout[0], f.buffer_mut()) {
+            app.transition = None;
+        }
+    }
 
-            if app.running_state == RunningState::ShowingDownloadPopup {
-                let msg =
-                    "Download successful. Check your downloads folder!\nPress any key to dismiss.";
-                let popup_area = centered_rect(
-                    app_layout[1],
-                    Constraint::Length(msg.len() as u16),
-                    // `+ 2` as the default message only contains 2 lines
-                    Constraint::Length(5),
-                );
+    // Split the image pane to compare the pinned image with the current one
+    let mut image_pane = app_layout[1];
+    if let Some(pinned) = &mut app.pinned {
+        let [pinned_pane, current_pane] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(app_layout[1]);
+        image_pane = current_pane;
+
+        let pinned_block = pane_block(Focus::Pinned).title(" Pinned <C-b> ");
+        render_image(
+            f,
+            pinned,
+            pinned_block.inner(pinned_pane),
+            app.config.cell_aspect_correction(),
+        );
+        f.render_widget(pinned_block, pinned_pane);
+    }
+
+    let image_title = match &app.image {
+        Some(image) if app.favorites.contains(&image.search_id) => " Image ★ ",
+        _ => " Image ",
+    };
+    let mut image_block = pane_block(Focus::Image).title(image_title);
+
+    // Explain why the selected image couldn't be shown
+    if !matches!(
+        browsing_state,
+        RunningState::BrowsingCategories | RunningState::BrowsingByTag
+    ) {
+        if let Some(reason) = app
+            .selected_image()
+            .and_then(|x| app.failed_images.get(&x.search_id))
+        {
+            image_block =
+                image_block.title_bottom(Line::from(format!(" ✗ {reason} ").red()).centered());
+        }
+    }
 
-                f.render_widget(Clear, popup_area);
+    let image_area = image_block.inner(image_pane);
+    let next_search_id = app.next_image().map(|x| x.search_id.clone());
+    app.image_area = image_area;
+    if let Some((_, background)) = app.background.as_mut().filter(|(search_id, _)| {
+        app.image
+            .as_ref()
+            .is_some_and(|x| &x.search_id == search_id)
+    }) {
+        match background {
+            Background::Dim(color) => f.render_widget(Block::new().bg(*color), image_area),
+            Background::Blur(state) => f.render_stateful_widget(
+                StatefulImage::new(None).resize(Resize::Fit(None)),
+                image_area,
+                state,
+            ),
+        }
+    }
+    if let Some(image) = &mut app.image {
+        // Fit to the pane until the image has been decoded for its real size
+        match app
+            .native_image
+            .as_mut()
+            .filter(|x| app.native_size && x.search_id == image.search_id)
+        {
+            Some(native_image) => native_image.render(f, image_area),
+            None => render_image(f, image, image_area, app.config.cell_aspect_correction()),
+        }
 
-                let popup = thick_block;
+        // Nothing is shown until the next image has loaded
+        if let Some(preview) = app
+            .preview
+            .as_mut()
+            .filter(|x| app.show_preview && next_search_id.as_ref() == Some(&x.search_id))
+        {
+            let [_, preview_area] =
+                Layout::vertical([Constraint::Fill(1), Constraint::Percentage(30)])
+                    .areas(image_area);
+            let [_, preview_area] =
+                Layout::horizontal([Constraint::Fill(1), Constraint::Percentage(30)])
+                    .areas(preview_area);
+            let preview_block = main_block.clone().title(" Next ");
 
-                let text = Paragraph::new(msg).block(popup).centered();
+            f.render_widget(Clear, preview_area);
+            render_image(
+                f,
+                preview,
+                preview_block.inner(preview_area),
+                app.config.cell_aspect_correction(),
+            );
+            f.render_widget(preview_block, preview_area);
+        }
+    } else {
+        let selected_failed = !matches!(
+            browsing_state,
+            RunningState::BrowsingCategories | RunningState::BrowsingByTag
+        ) && app
+            .selected_image()
+            .is_some_and(|x| app.failed_images.contains_key(&x.search_id));
+        let text = if let Some(bytes) = app.confirming_image {
+            format!(
+                "This image is {} — press Enter to load, Esc to skip",
+                format_size(bytes as usize)
+            )
+            .yellow()
+        } else if let Some(retry) = app.image_retry.as_ref().filter(|x| x.attempt > 0) {
+            format!(
+                "Retrying ({}/{})...",
+                retry.attempt, app.config.image_load_retries
+            )
+            .into()
+        } else if app.loading_image {
+            app.config
+                .spinner
+                .with_text(app.config.tick_ms, &app.config.loading_text)
+                .into()
+        } else if selected_failed {
+            "Unable to show this image".red()
+        } else if browsing_state == RunningState::BrowsingImages
+            && app.images_list.state.selected().is_none()
+        {
+            "Nothing selected".into()
+        } else {
+            "Select a book and press Enter".into()
+        };
 
-                f.render_widget(text, popup_area);
+        let [art_area, text_area] =
+            Layout::vertical([Constraint::Percentage(40), Constraint::Length(1)])
+                .spacing(1)
+                .flex(Flex::Center)
+                .areas(image_area);
+        // Only the idle states get the artwork, the others are about the image
+        // that was asked for
+        if !app.loading_image && !selected_failed {
+            if let Some(placeholder) = &mut app.placeholder {
+                render_image(
+                    f,
+                    placeholder,
+                    art_area,
+                    app.config.cell_aspect_correction(),
+                );
             }
-        },
+        }
+        f.render_widget(Paragraph::new(Line::from(text)).centered(), text_area);
     }
-}
+    f.render_widget(image_block, image_pane);
 
-#[allow(clippy::too_many_lines)]
-async fn update(app: &mut App, msg: Message) -> Option<Message> {
-    match msg {
-        Message::DismissDownloadPrompt => {
-            app.running_state = app.previous_running_state;
-        },
-        Message::HandleSearchInput(key) => {
-            app.search_input.handle_event(&Event::Key(key));
-        },
-        Message::Search => {
-            app.running_state = RunningState::Searching;
-        },
-        Message::ShowSearchResults => {
-            app.running_state = RunningState::BrowsingImages;
+    if app.running_state == RunningState::ShowingDownloadPopup {
+        let popup = app.download_popup();
+        let popup_area = popup.area(app_layout[1]);
+        f.render_widget(popup, popup_area);
+    } else if app.running_state == RunningState::ShowingDownloadQueue {
+        // `+ 2` to account for the block border, scroll past 10 items
+        let height = app.downloads.items.len().clamp(1, 10) as u16 + 2;
+        let popup_area = centered_rect(
+            app_layout[1],
+            Constraint::Percentage(80),
+            Constraint::Length(height),
+        );
 
-            // NOTE: We're not sorting this as the API returns the list already sorted with
-            // the best matching results first.
-            let images = aghpb::search(app.search_input.value().into(), None, None)
-                .await
-                .wrap_err_with(|| {
-                    format!(
-                        "unable to search using the query: {}",
-                        app.search_input.value()
-                    )
-                })
-                .suggestion("check your internet connectivity")
-                .unwrap();
+        f.render_widget(Clear, popup_area);
 
-            app.images = images.into_iter().map(Arc::new).collect();
+        let list = app
+            .downloads
+            .get_list()
+            .block(popup_block.title(" Downloads "))
+            .highlight_style(highlight_style);
+        f.render_stateful_widget(list, popup_area, &mut app.downloads.state);
+    } else if app.running_state == RunningState::ShowingCategoryPalette {
+        // `+ 2` to account for the block border, scroll past 10 items
+        let list_height = app.category_palette.len().clamp(1, 10) as u16 + 2;
+        let popup_area = centered_rect(
+            main_layout[0],
+            Constraint::Percentage(50),
+            Constraint::Length(3 + list_height),
+        );
+        let [input_area, list_area] =
+            Layout::vertical([Constraint::Length(3), Constraint::Length(list_height)])
+                .areas(popup_area);
 
-            app.images_list =
-                StatefulList::with_items(app.images.iter().map(|x| x.name.clone()).collect());
-        },
-        Message::Exit => {
-            app.running_state = RunningState::Exit;
-        },
-        Message::BrowseCategories => {
-            app.running_state = RunningState::BrowsingCategories;
-        },
-        Message::LoadCategories => {
-            let mut categories = aghpb::categories()
-                .await
-                .wrap_err("unable to retrieve category list")
-                .suggestion("check your internet connectivity")
-                .unwrap();
-            categories.sort_unstable();
-            app.categories = StatefulList::with_items(categories);
-            app.running_state = RunningState::BrowsingCategories;
-        },
-        Message::MoveUpCategories => app.categories.previous(),
-        Message::MoveUpImages => app.images_list.previous(),
-        Message::MoveDownCategories => app.categories.next(),
-        Message::MoveDownImages => app.images_list.next(),
-        Message::ShowImageList(category) => {
-            app.running_state = RunningState::BrowsingImages;
-
-            // NOTE: Searching with " " as the query gives us all of the images (as every
-            // image contains at least one " " in its title)
-            let mut images = aghpb::search(" ".to_owned(), Some(category.clone()), None)
-                .await
-                .wrap_err_with(|| {
-                    format!("unable to retrieve image list of category: `{category}`")
-                })
-                .suggestion("check your internet connectivity")
-                .unwrap();
+        f.render_widget(Clear, popup_area);
 
-            // PERF: Clone is expensive enough to warrant `cached_key`
-            images.sort_by_cached_key(|x| x.name.clone());
+        let input = Paragraph::new(app.category_palette_input.value())
+            .block(popup_block.clone().title(" Jump to Language "))
+            .scroll((
+                0,
+                app.category_palette_input
+                    .visual_scroll(usize::from(input_area.width.saturating_sub(2)))
+                    as u16,
+            ));
+        f.render_widget(input, input_area);
 
-            app.images = images.into_iter().map(Arc::new).collect();
+        let list = app
+            .category_palette
+            .get_list(list_area.width, app.config.list_overflow)
+            .block(popup_block)
+            .highlight_style(highlight_style);
+        f.render_stateful_widget(list, list_area, &mut app.category_palette.state);
+    } else if app.running_state == RunningState::ShowingHelp {
+        // `+ 3` to account for the block border and the header
+        let height = help::HELP_TABLE.len() as u16 + 3;
+        let popup_area = centered_rect(
+            main_layout[0],
+            Constraint::Percentage(60),
+            Constraint::Length(height),
+        );
 
-            app.images_list =
-                StatefulList::with_items(app.images.iter().map(|x| x.name.clone()).collect());
-        },
-        Message::LoadImage => {
-            app.image = None;
-            app.shown_at_least_one_image = true;
-
-            // Impossible for this to explode as an item is always selected, therefore it's
-            // safe to `unwrap` here
-            let selected_image_index = app.images_list.state.selected().unwrap();
-
-            let image_ref = app.images[selected_image_index].clone();
-
-            app.tasks.spawn(async move {
-                // Asynchronously fetch the book data
-                let book_data = image_ref.get_book().await.map_err(|e| {
-                    eyre!("{e}")
-                        .wrap_err("unable to retrieve book data")
-                        .suggestion("check your internet connectivity")
-                })?;
-                let image_data = book_data.raw_bytes.clone();
-
-                let dyn_image = image::load_from_memory(&image_data)
-                    .wrap_err("image cannot be processed from memory")
-                    .suggestion("check your internet connectivity")?;
-
-                let height = dyn_image.height() as u16;
-                let width = dyn_image.width() as u16;
-
-                // NOTE: Windows doesn't support `termios`
-                #[cfg(windows)]
-                let mut picker = Picker::new((7, 14));
-                #[cfg(unix)]
-                let mut picker = Picker::from_termios().unwrap_or_else(|_| Picker::new((7, 14)));
-
-                picker.guess_protocol();
-
-                // HACK: Protocol guesser doesn't pickup sixel for xterm in the app for some
-                // reason
-                if let Ok(term) = env::var("TERM") {
-                    if &term == "xterm" {
-                        picker.protocol_type = ProtocolType::Sixel;
-                    }
-                }
+        f.render_widget(Clear, popup_area);
 
-                let image_state = picker.new_resize_protocol(dyn_image);
+        let table = help::table().block(popup_block.title(" Help "));
+        f.render_stateful_widget(table, popup_area, &mut app.help_state);
+    } else if app.running_state == RunningState::FindingInList {
+        // Floats at the bottom of the list it searches, out of the way of the
+        // selection
+        let list_area = app_layout[0];
+        let input_area = Rect {
+            y: list_area.bottom().saturating_sub(3),
+            height: 3.min(list_area.height),
+            ..list_area
+        };
+        let query = app.find_input.value();
+        let list = if browsing_state == RunningState::BrowsingCategories {
+            &app.categories
+        } else {
+            &app.images_list
+        };
+        let found = find_matches(list, query);
+        // Where the selection is among the matches, counted from 1
+        let current = list
+            .state
+            .selected()
+            .and_then(|x| found.iter().position(|i| *i == x))
+            .map_or(0, |x| x + 1);
+        let title = if query.is_empty() {
+            Line::from(" Find in List ")
+        } else if found.is_empty() {
+            Line::from(" No match ".red())
+        } else {
+            Line::from(format!(" Find in List ({current}/{}) ", found.len()))
+        };
 
-                let image = Image {
-                    name: book_data.details.name,
-                    state: image_state,
-                    data: image_data,
-                    protocol: picker.protocol_type,
-                    height,
-                    width,
-                };
+        f.render_widget(Clear, input_area);
+        let input = Paragraph::new(query)
+            .block(popup_block.title(title))
+            .scroll((
+                0,
+                app.find_input
+                    .visual_scroll(usize::from(input_area.width.saturating_sub(2)))
+                    as u16,
+            ));
+        f.render_widget(input, input_area);
+    } else if app.running_state == RunningState::ShowingFavorites {
+        // `+ 2` to account for the block border, scroll past 10 items
+        let height = app.favorites_list.len().clamp(1, 10) as u16 + 2;
+        let popup_area = centered_rect(
+            app_layout[1],
+            Constraint::Percentage(80),
+            Constraint::Length(height),
+        );
 
-                // Send the loaded image back to the main loop
-                Ok(Message::ShowImage(image))
-            });
-        },
-        Message::ShowImage(image) => {
-            app.image = Some(image);
-        },
-        Message::DownloadImage => {
-            if let Some(image) = &app.image {
-                let mut download_path = dirs::download_dir()
-                    .wrap_err("unable to locate download directory")
-                    .unwrap();
-                download_path.push(format!("{}.jpeg", image.name));
+        f.render_widget(Clear, popup_area);
 
-                fs::write(download_path, &image.data)
-                    .wrap_err("unable to write the image data to disk")
-                    .suggestion("verify the existence of your downloads directory")
-                    .unwrap();
-                app.previous_running_state = app.running_state;
-                app.running_state = RunningState::ShowingDownloadPopup;
-            } else {
-                unreachable!("no image to download")
-            }
-        },
+        // Make it obvious where the favorite that was just moved ended up
+        let highlight_style = if app
+            .favorite_moved_at
+            .is_some_and(|x| x.elapsed() < MOVED_FAVORITE_HIGHLIGHT)
+        {
+            Style::default().bold().black().on_green()
+        } else {
+            highlight_style
+        };
+        let list = app
+            .favorites_list
+            .get_list(popup_area.width, app.config.list_overflow)
+            .block(popup_block.title(" Favorites "))
+            .highlight_style(highlight_style);
+        f.render_stateful_widget(list, popup_area, &mut app.favorites_list.state);
+    } else if app.running_state == RunningState::ShowingBlacklist {
+        // `+ 2` to account for the block border, scroll past 10 items
+        let height = app.blacklist_list.len().clamp(1, 10) as u16 + 2;
+        let popup_area = centered_rect(
+            app_layout[1],
+            Constraint::Percentage(80),
+            Constraint::Length(height),
+        );
+
+        f.render_widget(Clear, popup_area);
+        let list = app
+            .blacklist_list
+            .get_list(popup_area.width, app.config.list_overflow)
+            .block(popup_block.title(" Hidden images "))
+            .highlight_style(highlight_style);
+        f.render_stateful_widget(list, popup_area, &mut app.blacklist_list.state);
+    } else if app.running_state == RunningState::EditingNote {
+        // Written over the image the note is about
+        f.render_widget(Clear, image_pane);
+        f.render_widget(&app.note_editor, image_pane);
     }
+}
+
+/// Formats the number of bytes in the largest unit it's at least one of.
+#[allow(clippy::cast_precision_loss)]
+fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
 
-    None
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
 }
 
-fn handle_event(app: &App) -> Result<Option<Message>> {
-    if event::poll(Duration::from_millis(250))? {
-        if let Event::Key(key) = event::read()? {
-            if key.kind == event::KeyEventKind::Press {
-                return Ok(handle_key(app, key));
-            }
-        }
+/// Renders the search input and its suggestions floating over the lists.
+fn view_searching(app: &mut App, f: &mut Frame) {
+    let input_area = floating_overlay(f.size(), Constraint::Percentage(50), 3);
+    // The suggestions hang below the input, as far as the window allows
+    let suggestions_height = if app.suggestions.is_empty() {
+        0
+    } else {
+        // `+ 2` to account for the block border
+        (app.suggestions.len() as u16 + 2)
+            .min(f.size().bottom().saturating_sub(input_area.bottom()))
+    };
+    let suggestions_area = Rect {
+        y: input_area.bottom(),
+        height: suggestions_height,
+        ..input_area
+    };
+
+    let title = match (app.search_scope(), &app.search_category) {
+        (Some(category), _) => format!(" Search in {category} "),
+        (None, Some(_)) => " Search all ".to_owned(),
+        (None, None) => " Search ".to_owned(),
+    };
+    let mut block = Block::bordered()
+        .border_type(app.config.border_style.popup_blocks.into())
+        .title(title);
+    if app.search_category.is_some() {
+        block = block.title(Line::from(" Scope <C-t> ").right_aligned());
+    }
+
+    f.render_widget(Clear, input_area);
+    // Tell an empty search apart from one that's typed out, as Enter lists
+    // every image instead
+    let text = if app.search_input.value().is_empty() && app.config.empty_search_browses_all {
+        Line::from(match app.search_scope() {
+            Some(category) => format!("Enter to browse all of {category}"),
+            None => "Enter to browse all images".to_owned(),
+        })
+        .dim()
+        .italic()
+    } else {
+        Line::from(app.search_input.value())
+    };
+    let input = Paragraph::new(text).block(block).scroll((
+        0,
+        app.search_input
+            .visual_scroll(usize::from(input_area.width.saturating_sub(2))) as u16,
+    ));
+    f.render_widget(input, input_area);
+
+    if suggestions_height > 0 {
+        f.render_widget(Clear, suggestions_area);
+        let list = app
+            .suggestions
+            .get_list(suggestions_area.width, app.config.list_overflow)
+            .block(Block::bordered().title(" Suggestions <Tab> "))
+            .highlight_style(Style::default().bold().reversed().green());
+        f.render_stateful_widget(list, suggestions_area, &mut app.suggestions.state);
     }
-    Ok(None)
 }
 
-fn handle_key(app: &App, key: event::KeyEvent) -> Option<Message> {
-    match app.running_state {
-        RunningState::Searching => match key.code {
-            KeyCode::Enter if !app.search_input.value().is_empty() => {
-                // Only allow the user to press enter if they've entered some search query.
-                Some(Message::ShowSearchResults)
-            },
-            _ => Some(Message::HandleSearchInput(key)),
-        },
-        RunningState::BrowsingCategories => match key.code {
-            KeyCode::Up => Some(Message::MoveUpCategories),
-            KeyCode::Down => Some(Message::MoveDownCategories),
-            KeyCode::Right | KeyCode::Enter => Some(Message::ShowImageList(
-                app.categories.items[app.categories.state.selected().unwrap()].clone(),
-            )),
-            KeyCode::Char('q') => Some(Message::Exit),
-            KeyCode::Char('s' | '/') => Some(Message::Search),
-            KeyCode::Char('d') if app.image.is_some() => Some(Message::DownloadImage),
-            _ => None,
-        },
-        RunningState::BrowsingImages => match key.code {
-            KeyCode::Char('q') => Some(Message::Exit),
-            KeyCode::Char('s' | '/') => Some(Message::Search),
-            KeyCode::Char('d') if app.image.is_some() => Some(Message::DownloadImage),
-            KeyCode::Up => Some(Message::MoveUpImages),
-            KeyCode::Down => Some(Message::MoveDownImages),
-            KeyCode::Left => Some(Message::BrowseCategories),
-            KeyCode::Right | KeyCode::Enter => Some(Message::LoadImage),
-            _ => None,
-        },
-        RunningState::ShowingDownloadPopup => Some(Message::DismissDownloadPrompt),
-        RunningState::Exit | RunningState::Loading => None,
+fn images_title<'a>(
+    source: Option<&ListSource>,
+    loading: Option<&ListSource>,
+    sort_by: SortBy,
+    spinner: &str,
+) -> Line<'a> {
+    let mut title = match (loading, source) {
+        (Some(loading), _) => Line::from(vec![
+            loading.title().into(),
+            format!("({}) ", format!("{spinner} loading").trim_start()).dim(),
+        ]),
+        (None, Some(source)) => Line::from(source.title()),
+        (None, None) => return Line::from(" Select Image "),
+    };
+    if sort_by != SortBy::Source {
+        title.push_span(format!("(by {}) ", sort_by.label()).dim());
     }
+    title
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    errors::install_hooks()?;
-    let mut term = tui::init()?;
-    let mut app = App::default();
-    let mut first_launch = true;
+/// Returns the items matching the find input. With the `regex` feature, the
+/// input is matched as a regular expression once it's a valid one.
+fn find_matches(list: &StatefulList, query: &str) -> Vec<usize> {
+    #[cfg(feature = "regex")]
+    if let Some(pattern) = find_pattern(query) {
+        return list.find_all_regex(&pattern);
+    }
+    list.find_all(query)
+}
 
-    while app.running_state != RunningState::Exit {
-        term.draw(|f| view(&mut app, f))?;
+/// Returns the next item matching the find input after `from`, or the
+/// previous one if `forward` is false, wrapping around. `from` itself is
+/// checked last.
+fn next_find_match(list: &StatefulList, query: &str, from: usize, forward: bool) -> Option<usize> {
+    #[cfg(feature = "regex")]
+    if let Some(pattern) = find_pattern(query) {
+        let found = list.find_all_regex(&pattern);
+        let next = if forward {
+            found.iter().find(|i| **i > from).or(found.first())
+        } else {
+            found.iter().rev().find(|i| **i < from).or(found.last())
+        };
+        return next.copied();
+    }
+    if forward {
+        list.search_wrap(query, from)
+    } else {
+        list.search_wrap_backward(query, from)
+    }
+}
 
-        let mut message = handle_event(&app)?;
+// NOTE: Half typed patterns like `c++` aren't valid, they're searched for as
+// they are until they are
+#[cfg(feature = "regex")]
+fn find_pattern(query: &str) -> Option<regex::Regex> {
+    regex::RegexBuilder::new(query)
+        .case_insensitive(true)
+        .build()
+        .ok()
+}
 
-        if first_launch {
-            message = Some(Message::LoadCategories);
-            first_launch = false;
+/// Formats the duration as a rough "3 days ago".
+fn format_age(age: Duration) -> String {
+    let (count, unit) = match age.as_secs() {
+        secs @ 0..3600 => (secs / 60, "minute"),
+        secs @ 3600..86400 => (secs / 3600, "hour"),
+        secs => (secs / 86400, "day"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural} ago")
+}
+
+fn categories_title<'a>(cache_age: Option<Duration>) -> Line<'a> {
+    let mut title = Line::from(" Select Language ");
+
+    if let Some(age) = cache_age {
+        let hours = age.as_secs() / 3600;
+        let suffix = if hours == 0 {
+            format!("(cached {}m ago) ", age.as_secs() / 60)
+        } else {
+            format!("(cached {hours}h ago) ")
+        };
+
+        title.push_span(match hours {
+            0..6 => suffix.into(),
+            6..24 => suffix.yellow(),
+            _ => suffix.red(),
+        });
+    }
+
+    title
+}
+
+impl App {
+    /// Clears everything that was browsed, as if the app had just started.
+    /// The config, the picker and everything persisted across runs, like the
+    /// favorites, are kept. Running tasks, downloads included, are aborted.
+    fn reset(&mut self) {
+        for download in &self.downloads.items {
+            download.cancel();
+        }
+        let App {
+            config,
+            placeholder,
+            seen,
+            favorites,
+            blacklist,
+            notes,
+            known_books,
+            session,
+            picker,
+            latency,
+            temp_files,
+            ..
+        } = std::mem::take(self);
+        *self = App {
+            config,
+            placeholder,
+            seen,
+            favorites,
+            blacklist,
+            notes,
+            known_books,
+            session,
+            picker,
+            latency,
+            temp_files,
+            ..Default::default()
+        };
+    }
+
+    #[allow(clippy::too_many_lines)]
+    async fn update(&mut self, msg: Message) -> Vec<Message> {
+        match msg {
+            Message::DismissDownloadPrompt => {
+                self.running_state = self.previous_running_state;
+                self.download_popup_opened_at = None;
+            },
+            Message::CloseCategoryPalette | Message::AcceptFind => {
+                self.running_state = self.previous_running_state;
+            },
+            Message::ApiChecked(version) => {
+                // NOTE: Failing to check is no reason to keep the user from browsing
+                self.api_warning = version.filter(|x| !x.is_supported());
+                self.api_warning_opened_at = self.api_warning.map(|_| Instant::now());
+            },
+            Message::DismissApiWarning => {
+                self.api_warning = None;
+                self.api_warning_opened_at = None;
+            },
+            Message::StartScreensaver => {
+                self.screensaver = Some(Screensaver {
+                    image: None,
+                    next_at: None,
+                });
+                self.spawn_screensaver_image();
+            },
+            Message::NextScreensaverImage => self.spawn_screensaver_image(),
+            Message::ScreensaverImage(image) => self.handle_screensaver_image(image),
+            Message::StopScreensaver => {
+                self.screensaver = None;
+                self.last_input = Some(Instant::now());
+            },
+            Message::HandleSearchInput(key) => self.handle_search_input(key),
+            Message::FetchSuggestions => self.handle_fetch_suggestions(),
+            Message::ShowSuggestions(query, suggestions) => {
+                self.handle_show_suggestions(&query, suggestions);
+            },
+            Message::MoveDownSuggestions => self.suggestions.next(),
+            Message::MoveUpSuggestions => self.handle_move_up_suggestions(),
+            Message::CompleteSuggestion => self.handle_complete_suggestion(),
+            Message::Search => self.handle_search(),
+            Message::ToggleSearchScope => self.handle_toggle_search_scope(),
+            Message::ShowSearchResults => return self.handle_show_search_results(),
+            Message::Exit => self.running_state = RunningState::Exit,
+            Message::Reset => {
+                self.reset();
+                return vec![Message::LoadCategories];
+            },
+            Message::BrowseCategories => self.running_state = RunningState::BrowsingCategories,
+            Message::LoadCategories => {
+                self.handle_load_categories().await;
+                self.prefetch_adjacent_categories();
+                return self
+                    .deep_link_target
+                    .clone()
+                    .map(Message::ShowImageByName)
+                    .into_iter()
+                    .collect();
+            },
+            Message::ShowImageByName(name) => self.handle_show_image_by_name(name),
+            Message::DeepLinkResolved(name, book) => {
+                return self.handle_deep_link_resolved(&name, book);
+            },
+            Message::CategoriesRefreshed(categories) => {
+                self.handle_categories_refreshed(categories);
+            },
+            Message::MoveUpCategories => {
+                self.categories.previous();
+                self.start_hover_timer();
+                self.announce_selected_category();
+            },
+            Message::MoveUpImages => {
+                self.skip_image_load();
+                self.images_list.previous();
+            },
+            Message::MoveDownCategories => {
+                self.categories.next();
+                self.start_hover_timer();
+                self.announce_selected_category();
+            },
+            Message::JumpToLetter(letter) => {
+                if self
+                    .categories
+                    .select_by_prefix(letter.encode_utf8(&mut [0; 4]))
+                {
+                    self.start_hover_timer();
+                    self.announce_selected_category();
+                } else {
+                    self.toast = Some(Toast::new(format!(
+                        "No category starts with {}",
+                        letter.to_ascii_uppercase()
+                    )));
+                }
+            },
+            Message::PreloadCategory(category) => self.handle_preload_category(category),
+            Message::ChangePrefetchedCategories(direction) => {
+                self.handle_change_prefetched_categories(direction);
+            },
+            Message::CategoryPreloaded(category, images) => {
+                return self.handle_category_preloaded(category, images);
+            },
+            Message::LoadTags => self.handle_load_tags(),
+            Message::MoveUpTags => self.tags_list.previous(),
+            Message::MoveDownTags => self.tags_list.next(),
+            Message::MoveDownImages => {
+                self.skip_image_load();
+                self.images_list.next();
+            },
+            Message::LoadList(source) => return self.handle_load_list(source),
+            Message::SwitchList => self.handle_switch_list(),
+            Message::CycleSort => self.handle_cycle_sort(),
+            Message::ResetView => self.handle_reset_view(),
+            Message::FocusChanged(focused) => {
+                self.paused = !focused && self.config.pause_when_unfocused;
+                // Catch up on the preview skipped while paused
+                self.load_preview();
+            },
+            Message::AutoRefreshList => self.handle_auto_refresh_list(),
+            Message::ListLoaded(source, images) => return self.handle_list_loaded(source, images),
+            Message::ListLoadFailed(source, reason) => {
+                self.handle_list_load_failed(&source, &reason);
+            },
+            Message::ExpandResults => self.handle_expand_results(),
+            Message::ForceReloadImageList => return self.handle_force_reload_image_list(),
+            Message::LoadImage => self.handle_load_image(false),
+            Message::ForceLoadImage => self.handle_load_image(true),
+            Message::ReloadImage => self.handle_reload_image(),
+            Message::ShowImage(image) => self.handle_show_image(image),
+            Message::TogglePreview => self.handle_toggle_preview(),
+            Message::ToggleNativeSize => self.handle_toggle_native_size(),
+            Message::NativeImageDecoded(image) => self.native_image = image,
+            Message::ToggleMark => self.handle_toggle_mark(),
+            Message::Compare => self.handle_compare(),
+            Message::CompareLoaded(result) => self.handle_compare_loaded(result),
+            Message::ZoomImage(direction) => self.handle_zoom_image(direction),
+            Message::SwapCompared => self.handle_swap_compared(),
+            Message::CloseCompare => {
+                self.compared = None;
+                self.running_state = self.previous_running_state;
+            },
+            Message::PanImage(x, y) => self.handle_pan_image(x, y),
+            Message::PreviewLoaded(image) => self.preview = image,
+            Message::BackgroundComputed(search_id, background) => {
+                self.background = background.map(|x| (search_id, x));
+            },
+            Message::AccentComputed(search_id, color) => {
+                self.accent = color.map(|x| (search_id, x));
+            },
+            Message::ImageDecodeFailed { search_id, reason } => {
+                self.handle_image_decode_failed(search_id, reason);
+            },
+            Message::ImageFetchFailed {
+                search_id,
+                reason,
+                transient,
+            } => self.handle_image_fetch_failed(search_id, reason, transient),
+            Message::RetryImageLoad(search_id) => self.handle_retry_image_load(&search_id),
+            Message::ConfirmImageLoad { search_id, bytes } => {
+                self.handle_confirm_image_load(&search_id, bytes);
+            },
+            Message::AcceptImageLoad => self.handle_accept_image_load(),
+            Message::SkipImageLoad => self.skip_image_load(),
+            Message::ToggleFavorite => self.handle_toggle_favorite(),
+            Message::ExportBookmarks => self.handle_export_bookmarks(),
+            Message::ExportList => self.handle_export_list(),
+            Message::ClearSeen => self.handle_clear_seen(),
+            Message::CopyDataUri => self.handle_copy_data_uri(),
+            Message::CopyToTempFile => self.handle_copy_to_temp_file(),
+            Message::CopyState => self.handle_copy_state(),
+            Message::DownloadImage => self.handle_download_image().await,
+            Message::DownloadThumbnails => self.handle_download_thumbnails(),
+            Message::TogglePin => self.handle_toggle_pin(),
+            Message::EditNote => self.handle_edit_note(),
+            Message::HandleNoteInput(key) => {
+                self.note_editor.input(key);
+            },
+            Message::SaveNote => self.handle_save_note(),
+            Message::CancelNote => self.running_state = self.previous_running_state,
+            Message::PinImage(image) => self.pinned = Some(image),
+            Message::SwitchFocus => self.handle_switch_focus(),
+            Message::ShowPreviousImage => {
+                self.step_images(false);
+                return self.load_selected_image();
+            },
+            Message::ShowNextImage => {
+                self.step_images(true);
+                return self.load_selected_image();
+            },
+            Message::DownloadFinished(id, result) => self.handle_download_finished(id, result),
+            Message::ToggleDownloadQueue => self.toggle_popup(RunningState::ShowingDownloadQueue),
+            Message::ToggleHelp => {
+                self.toggle_popup(RunningState::ShowingHelp);
+                self.help_state = TableState::default().with_selected(Some(0));
+            },
+            Message::MoveUpHelp => self.handle_move_up_help(),
+            Message::MoveDownHelp => self.handle_move_down_help(),
+            Message::ToggleFavorites => self.handle_toggle_favorites(),
+            Message::Undo => self.handle_undo(),
+            Message::GoBack => self.handle_go_back(),
+            Message::OpenDownloadFolder => self.handle_open_download_folder(),
+            Message::CopyDownloadPath => self.handle_copy_download_path(),
+            Message::DownloadsScanned(scanned) => self.on_disk.extend(scanned),
+            Message::NextNotDownloaded => self.handle_jump_not_downloaded(true),
+            Message::PreviousNotDownloaded => self.handle_jump_not_downloaded(false),
+            Message::OpenFind => self.handle_open_find(),
+            Message::HandleFindInput(key) => self.handle_find_input(key),
+            Message::FindNext => self.handle_find(true),
+            Message::FindPrevious => self.handle_find(false),
+            Message::CancelFind => self.handle_cancel_find(),
+            Message::MoveUpFavorites => self.favorites_list.previous(),
+            Message::MoveDownFavorites => self.favorites_list.next(),
+            Message::MoveFavoriteUp => self.handle_move_favorite(false),
+            Message::MoveFavoriteDown => self.handle_move_favorite(true),
+            Message::HideImage => return self.handle_hide_image(),
+            Message::ToggleBlacklist => self.handle_toggle_blacklist(),
+            Message::MoveUpBlacklist => self.blacklist_list.previous(),
+            Message::MoveDownBlacklist => self.blacklist_list.next(),
+            Message::UnhideImage => self.handle_unhide_image(),
+            Message::OpenCategoryPalette => self.handle_open_category_palette(),
+            Message::HandleCategoryPaletteInput(key) => self.handle_category_palette_input(key),
+            Message::MoveUpCategoryPalette => self.category_palette.previous(),
+            Message::MoveDownCategoryPalette => self.category_palette.next(),
+            Message::MoveUpDownloads => self.downloads.previous(),
+            Message::MoveDownDownloads => self.downloads.next(),
+            Message::RetryDownload => self.handle_retry_download(),
+            Message::CancelDownload => self.handle_cancel_download(),
         }
 
-        while let Some(msg) = message {
-            message = update(&mut app, msg).await;
+        vec![]
+    }
+
+    /// Opens the popup, or closes it if it's already open.
+    fn toggle_popup(&mut self, popup: RunningState) {
+        if self.running_state == popup {
+            self.running_state = self.previous_running_state;
+        } else {
+            self.previous_running_state = self.running_state;
+            self.running_state = popup;
         }
+    }
 
-        while let Some(msg) = app.tasks.try_join_next() {
-            update(&mut app, msg.unwrap().unwrap()).await;
+    fn handle_search_input(&mut self, key: KeyEvent) {
+        if self
+            .search_input
+            .handle_event(&Event::Key(key))
+            .is_some_and(|x| x.value)
+        {
+            self.suggestions_deadline = Some(Instant::now() + SUGGESTIONS_DEBOUNCE);
         }
     }
 
-    tui::restore()?;
-    Ok(())
+    fn handle_search(&mut self) {
+        self.previous_running_state = self.running_state;
+        self.running_state = RunningState::Searching;
+        self.suggestions = StatefulList::default();
+
+        self.search_category = match (self.previous_running_state, &self.list_source) {
+            (RunningState::BrowsingImages, Some(ListSource::Category(category))) => {
+                Some(category.clone())
+            },
+            (RunningState::BrowsingCategories, _) => self
+                .categories
+                .state
+                .selected()
+                .and_then(|i| self.categories.get(i))
+                .map(str::to_owned),
+            _ => None,
+        };
+    }
+
+    fn handle_load_tags(&mut self) {
+        let tags = tags::from_categories(self.categories.iter());
+        // Keep the selection when coming back from a tag's image list
+        if tags.iter().map(|(tag, _)| tag).ne(&self.tags) {
+            self.tags = tags.iter().map(|(tag, _)| tag.clone()).collect();
+            self.tags_list = StatefulList::with_items(
+                tags.iter()
+                    .map(|(tag, categories)| format!("{tag} ({})", categories.len()))
+                    .collect(),
+            );
+        }
+        self.running_state = RunningState::BrowsingByTag;
+    }
+
+    fn handle_go_back(&mut self) {
+        let Some(ListSource::Search(_, scope)) = &self.list_source else {
+            // Nothing to search again for in an empty category
+            return;
+        };
+        let scope = scope.clone();
+        // NOTE: The search input still holds the query, so it's ready to be edited
+        self.handle_search();
+        self.search_category = scope;
+    }
+
+    fn handle_toggle_search_scope(&mut self) {
+        if self.search_category.is_none() {
+            self.toast = Some(Toast::new(
+                "Search from a category to limit the search to it",
+            ));
+            return;
+        }
+        self.session.search_all = !self.session.search_all;
+        self.suggestions_deadline = Some(Instant::now());
+    }
+
+    /// Returns the category searches are limited to, `None` for every category.
+    fn search_scope(&self) -> Option<String> {
+        self.search_category
+            .clone()
+            .filter(|_| !self.session.search_all)
+    }
+
+    fn handle_fetch_suggestions(&mut self) {
+        self.suggestions_deadline = None;
+
+        let query = self.search_input.value().to_owned();
+        if query.is_empty() {
+            self.suggestions = StatefulList::default();
+            return;
+        }
+
+        let scope = self.search_scope();
+        let latency = self.latency.clone();
+        self.tasks.spawn(async move {
+            // NOTE: The API has no dedicated autocomplete endpoint, so the best
+            // matching book names are used instead. Suggestions are optional,
+            // therefore failures are silently ignored.
+            let mut suggestions = latency
+                .time(
+                    Kind::Api,
+                    "suggestions",
+                    http::client().search(&query, scope.as_deref(), Some(SUGGESTIONS_LIMIT)),
+                )
+                .await
+                .map(|x| {
+                    x.into_iter()
+                        .map(|x| names::normalize(&x.name))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            // Same named books can show up in multiple categories
+            let mut unique = HashSet::new();
+            suggestions.retain(|x| unique.insert(x.clone()));
+
+            Ok(Message::ShowSuggestions(query, suggestions))
+        });
+    }
+
+    fn handle_show_suggestions(&mut self, query: &str, suggestions: Vec<String>) {
+        // Ignore suggestions for a query that has since been edited
+        if self.running_state == RunningState::Searching && query == self.search_input.value() {
+            self.suggestions = StatefulList::with_items(suggestions);
+            self.suggestions.deselect();
+        }
+    }
+
+    fn handle_move_up_suggestions(&mut self) {
+        // Moving up from the first suggestion gives focus back to the input
+        if self.suggestions.state.selected() == Some(0) {
+            self.suggestions.deselect();
+        } else {
+            self.suggestions.previous();
+        }
+    }
+
+    fn handle_complete_suggestion(&mut self) {
+        if let Some(suggestion) = self
+            .suggestions
+            .state
+            .selected()
+            .and_then(|i| self.suggestions.get(i))
+        {
+            self.search_input = Input::new(suggestion.to_owned());
+            self.suggestions = StatefulList::default();
+        }
+    }
+
+    fn handle_show_search_results(&mut self) -> Vec<Message> {
+        self.suggestions_deadline = None;
+        match book_link::parse(self.search_input.value(), &http::client().api_url) {
+            Some(Ok(link)) => return self.open_book_link(link),
+            Some(Err(reason)) => {
+                self.toast = Some(Toast::new(format!("Searching for the link, as {reason}")));
+            },
+            None => {},
+        }
+        vec![Message::LoadList(ListSource::Search(
+            self.search_input.value().to_owned(),
+            self.search_scope(),
+        ))]
+    }
+
+    async fn handle_load_categories(&mut self) {
+        if let Some(entry) = self
+            .cache_max_age()
+            .and_then(|_| cache::load_categories().ok().flatten())
+        {
+            // Serve the cached categories right away, even stale ones
+            if entry.is_stale(self.cache_max_age()) {
+                let latency = self.latency.clone();
+                self.tasks.spawn(async move {
+                    let categories = latency
+                        .time(Kind::Api, "categories", http::client().categories())
+                        .await;
+                    Ok(Message::CategoriesRefreshed(categories.ok()))
+                });
+            }
+            self.category_cache_age = Some(entry.age());
+            self.install_categories(entry.data);
+            return;
+        }
+
+        let categories = match self
+            .latency
+            .time(Kind::Api, "categories", http::client().categories())
+            .await
+        {
+            Ok(categories) => {
+                // NOTE: The cache is only a fallback, failing to write it isn't worth
+                // bothering the user over
+                let _ = cache::save_categories(&categories);
+                self.category_cache_age = None;
+                categories
+            },
+            Err(e) => {
+                let entry = cache::load_categories()
+                    .unwrap_or_default()
+                    .wrap_err(e)
+                    .wrap_err("unable to retrieve category list")
+                    .suggestion("check your internet connectivity")
+                    .unwrap();
+                self.category_cache_age = Some(entry.age());
+                entry.data
+            },
+        };
+        self.install_categories(categories);
+    }
+
+    fn install_categories(&mut self, mut categories: Vec<String>) {
+        categories.sort_unstable();
+        self.categories = StatefulList::with_items(categories);
+        if let Some(state) = self.session.categories.take() {
+            self.categories.restore_state(state);
+        }
+        self.running_state = RunningState::BrowsingCategories;
+    }
+
+    /// Swaps in the categories fetched again in the background, keeping the
+    /// selected one selected.
+    fn handle_categories_refreshed(&mut self, categories: Option<Vec<String>>) {
+        // NOTE: The cached categories are still good enough if the API is down
+        let Some(mut categories) = categories else {
+            return;
+        };
+        let _ = cache::save_categories(&categories);
+        self.category_cache_age = None;
+
+        categories.sort_unstable();
+        let selected = self
+            .categories
+            .state
+            .selected()
+            .and_then(|i| self.categories.get(i))
+            .map(str::to_owned);
+        self.categories = StatefulList::with_items(categories);
+        if let Some(i) = selected.and_then(|x| self.categories.iter().position(|y| y == x)) {
+            self.categories.state.select(Some(i));
+        }
+    }
+
+    fn handle_show_image_by_name(&mut self, name: String) {
+        self.deep_link_target = Some(name.clone());
+        let latency = self.latency.clone();
+        self.tasks.spawn(async move {
+            // NOTE: The search is fuzzy, so the exact name is picked out of the results
+            let books = latency
+                .time(
+                    Kind::Api,
+                    "search",
+                    http::client().search(&name, None, None),
+                )
+                .await
+                .unwrap_or_default();
+            let book = books
+                .into_iter()
+                .find(|x| same_name(&x.name, &name))
+                .map(Arc::new);
+            Ok(Message::DeepLinkResolved(name, book))
+        });
+    }
+
+    /// Opens the category of the book the link points to, selecting and
+    /// loading the book once it's there.
+    fn open_book_link(&mut self, link: BookLink) -> Vec<Message> {
+        match link {
+            BookLink::Repo { category, name } => {
+                // Picked up by `handle_list_loaded`
+                self.deep_link_target = Some(name);
+                self.handle_load_list(ListSource::Category(category))
+            },
+            // NOTE: Only the API knows the name and the category of the book
+            BookLink::Api { search_id } => {
+                self.deep_link_target = Some(search_id.clone());
+                let latency = self.latency.clone();
+                self.tasks.spawn(async move {
+                    let book = latency
+                        .time(Kind::Api, "book", http::client().get_id(&search_id))
+                        .await
+                        .ok()
+                        .map(|x| Arc::new(x.details));
+                    Ok(Message::DeepLinkResolved(search_id, book))
+                });
+                Vec::new()
+            },
+        }
+    }
+
+    fn handle_deep_link_resolved(
+        &mut self,
+        name: &str,
+        book: Option<Arc<BookData>>,
+    ) -> Vec<Message> {
+        // Another image was asked for meanwhile
+        if self.deep_link_target.as_deref() != Some(name) {
+            return vec![];
+        }
+        let Some(book) = book else {
+            self.deep_link_target = None;
+            self.toast = Some(Toast::new(format!("No image named \"{name}\"")));
+            return vec![];
+        };
+        // Picked up by `handle_list_loaded`
+        self.deep_link_target = Some(book.name.clone());
+        self.handle_load_list(ListSource::Category(book.category.clone()))
+    }
+
+    /// Selects the image the deep link points to in the list just shown.
+    /// Returns whether it's there.
+    fn select_deep_link_target(&mut self, name: &str) -> bool {
+        let found = self.images[..self.visible_images()]
+            .iter()
+            .position(|x| same_name(&x.name, name));
+        if found.is_some() {
+            self.images_list.state.select(found);
+        } else {
+            self.toast = Some(Toast::new(format!("No image named \"{name}\" in the list")));
+        }
+        found.is_some()
+    }
+
+    fn cache_max_age(&self) -> Option<Duration> {
+        self.config
+            .cache_max_age_minutes
+            .map(|x| Duration::from_secs(x * 60))
+    }
+
+    fn handle_load_list(&mut self, source: ListSource) -> Vec<Message> {
+        self.running_state = RunningState::BrowsingImages;
+        self.loading_list = Some(source.clone());
+        self.auto_refreshing = false;
+        // NOTE: Scheduled again for the new list once it's loaded
+        self.auto_refresh_deadline = None;
+
+        if let ListSource::Category(category) = &source {
+            if !self.category_cache.contains_key(category) && self.cache_max_age().is_some() {
+                if let Some(entry) = cache::load_image_list(category).ok().flatten() {
+                    self.category_cache.insert(category.clone(), entry);
+                }
+            }
+            // The preload hands the list over once it's done
+            if self.preloading.contains(category) {
+                return vec![];
+            }
+            if let Some(entry) = self.category_cache.get(category) {
+                let mut messages = vec![Message::ListLoaded(source, entry.data.clone())];
+                // Serve the stale list right away, and fetch it again once it's shown
+                if entry.is_stale(self.cache_max_age()) {
+                    messages.push(Message::AutoRefreshList);
+                }
+                return messages;
+            }
+        }
+
+        spawn_load_list(&mut self.tasks, self.latency.clone(), source);
+        vec![]
+    }
+
+    fn start_hover_timer(&mut self) {
+        self.hover_timer = self
+            .categories
+            .state
+            .selected()
+            .and_then(|i| self.categories.get(i))
+            .map(|x| (x.to_owned(), Instant::now()));
+    }
+
+    /// Returns the message preloading the category the selection rested on,
+    /// once it's rested there long enough.
+    fn preload_due(&mut self) -> Option<Message> {
+        if self.prefetching_paused() {
+            return None;
+        }
+        let (category, _) = self
+            .hover_timer
+            .take_if(|(_, since)| since.elapsed() >= HOVER_PRELOAD_DELAY)?;
+        (self.running_state == RunningState::BrowsingCategories)
+            .then_some(Message::PreloadCategory(category))
+    }
+
+    /// Preloads the categories at the top of the list, where browsing most
+    /// likely starts.
+    fn prefetch_adjacent_categories(&mut self) {
+        if self.prefetching_paused() || self.running_state != RunningState::BrowsingCategories {
+            return;
+        }
+        for category in self
+            .categories
+            .iter()
+            .take(self.prefetched_categories)
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+        {
+            self.handle_preload_category(category);
+        }
+    }
+
+    fn handle_change_prefetched_categories(&mut self, direction: i8) {
+        self.prefetched_categories = self
+            .prefetched_categories
+            .saturating_add_signed(isize::from(direction))
+            .min(MAX_PREFETCHED_CATEGORIES);
+        // The categories already preloaded are skipped, only the new ones are fetched
+        if direction > 0 {
+            self.prefetch_adjacent_categories();
+        }
+    }
+
+    fn handle_preload_category(&mut self, category: String) {
+        let fresh = self
+            .category_cache
+            .get(&category)
+            .is_some_and(|x| !x.is_stale(self.cache_max_age()));
+        if fresh || !self.preloading.insert(category.clone()) {
+            return;
+        }
+
+        let latency = self.latency.clone();
+        self.tasks.spawn(async move {
+            let source = ListSource::Category(category.clone());
+            // Let the tasks that were asked for go first
+            tokio::time::sleep(Duration::ZERO).await;
+            // NOTE: Nobody asked for the list yet, so failing is left to the visit
+            let images = latency
+                .time(Kind::Api, "preload", source.fetch())
+                .await
+                .ok();
+            Ok(Message::CategoryPreloaded(category, images))
+        });
+    }
+
+    fn handle_category_preloaded(
+        &mut self,
+        category: String,
+        images: Option<Vec<Arc<BookData>>>,
+    ) -> Vec<Message> {
+        self.preloading.remove(&category);
+        let source = ListSource::Category(category.clone());
+        let waiting = self.loading_list.as_ref() == Some(&source);
+
+        let Some(images) = images else {
+            // The category was opened meanwhile, fetch it the usual way
+            if waiting {
+                spawn_load_list(&mut self.tasks, self.latency.clone(), source);
+            }
+            return vec![];
+        };
+        if waiting {
+            self.category_cache.remove(&category);
+            return vec![Message::ListLoaded(source, images)];
+        }
+
+        self.category_cache
+            .insert(category.clone(), CacheEntry::new(images));
+        self.preloaded.insert(category);
+        vec![]
+    }
+
+    fn handle_auto_refresh_list(&mut self) {
+        self.auto_refresh_deadline = None;
+        // The list the user asked for takes over once it's in, don't drop it as
+        // stale for the old one
+        if self.loading_list.is_some() {
+            return;
+        }
+
+        // NOTE: Unlike a manual refresh this doesn't touch the running state, the
+        // user might be doing something else entirely
+        if let Some(source) = self.list_source.clone() {
+            if let ListSource::Category(category) = &source {
+                self.category_cache.remove(category);
+            }
+            self.loading_list = Some(source.clone());
+            self.auto_refreshing = true;
+            spawn_load_list(&mut self.tasks, self.latency.clone(), source);
+        }
+    }
+
+    fn handle_list_loaded(
+        &mut self,
+        source: ListSource,
+        images: Vec<Arc<BookData>>,
+    ) -> Vec<Message> {
+        // A newer list was asked for in the meantime
+        if self.loading_list.as_ref() != Some(&source) {
+            return vec![];
+        }
+        self.loading_list = None;
+        self.schedule_auto_refresh();
+        if self.auto_refreshing {
+            self.auto_refreshing = false;
+            self.toast = Some(Toast::new("Image list refreshed"));
+        }
+
+        if let ListSource::Category(category) = &source {
+            // Only freshly fetched lists can have new books in them
+            if !self.category_cache.contains_key(category) || self.preloaded.remove(category) {
+                self.find_new_books(category, &images);
+                // NOTE: The cache only saves a trip to the API, failing to write it isn't
+                // worth bothering the user over
+                let _ = cache::save_image_list(category, &images);
+                self.category_cache
+                    .insert(category.clone(), CacheEntry::new(images.clone()));
+            }
+        }
+
+        let refreshed = self.list_source.as_ref() == Some(&source);
+        let empty_results = images.is_empty();
+        self.install_list(source, images, refreshed);
+
+        if let Some(target) = self.deep_link_target.take() {
+            if self.select_deep_link_target(&target) {
+                return vec![Message::LoadImage];
+            }
+        }
+
+        // Leave the state alone if the user moved on to something else meanwhile
+        if empty_results && self.running_state == RunningState::BrowsingImages {
+            self.running_state = RunningState::EmptyResults;
+            return vec![];
+        }
+
+        // Save the user a keypress by showing the selected image right away
+        if self.config.auto_load_first_image && !refreshed && !self.images.is_empty() {
+            return vec![Message::LoadImage];
+        }
+        vec![]
+    }
+
+    /// Gives up on the list, leaving the list that was shown before in place.
+    fn handle_list_load_failed(&mut self, source: &ListSource, reason: &str) {
+        // A newer list was asked for in the meantime
+        if self.loading_list.as_ref() != Some(source) {
+            return;
+        }
+        self.loading_list = None;
+        if self.auto_refreshing {
+            // NOTE: The list shown is still good, try again at the next refresh
+            self.auto_refreshing = false;
+            self.schedule_auto_refresh();
+        }
+        self.toast = Some(Toast::new(format!("Unable to load the list: {reason}")));
+
+        // There's no list to fall back on
+        if self.list_source.is_none() && self.running_state == RunningState::BrowsingImages {
+            self.running_state = RunningState::BrowsingCategories;
+        }
+    }
+
+    fn schedule_auto_refresh(&mut self) {
+        self.auto_refresh_deadline = self
+            .config
+            .auto_refresh_minutes
+            .map(|x| Instant::now() + Duration::from_secs(x * 60));
+    }
+
+    /// Marks the books that weren't in the category on the previous visit as
+    /// new.
+    fn find_new_books(&mut self, category: &str, images: &[Arc<BookData>]) {
+        let new = self
+            .known_books
+            .visit(category, images)
+            .wrap_err("unable to remember the books of the category")
+            .unwrap();
+        if let Some((new, since)) = new.filter(|(new, _)| !new.is_empty()) {
+            let plural = if new.len() == 1 { "" } else { "s" };
+            self.toast = Some(Toast::new(format!(
+                "{} new book{plural} in {category} since {}",
+                new.len(),
+                format_age(since),
+            )));
+            self.new_books.extend(new);
+        }
+    }
+
+    /// Shows the image list, keeping the selected image selected when the same
+    /// list is loaded again.
+    fn install_list(&mut self, source: ListSource, images: Vec<Arc<BookData>>, refreshed: bool) {
+        if !refreshed {
+            self.put_aside_list();
+        }
+        let selected = self
+            .images_list
+            .state
+            .selected()
+            .filter(|_| refreshed)
+            .map(|i| self.images[i].search_id.clone());
+
+        self.source_images.clone_from(&images);
+        self.images = images;
+        // NOTE: Hidden images are still in `source_images`, so un-hiding one brings
+        // it back without fetching the list again
+        self.images
+            .retain(|x| !self.blacklist.contains(&x.search_id));
+        self.rank_results(&source);
+        self.list_source = Some(source);
+
+        // NOTE: The API doesn't expose a separate count endpoint, but the search
+        // response already tells us how many items we're about to push, plus the
+        // row for the weaker matches
+        // NOTE: Book names are long enough to wrap often, indenting the rest of the
+        // lines keeps each name together
+        self.images_list = StatefulList::with_capacity(self.visible_images() + 1)
+            .with_wrap_options(StatefulList::default_wrap_options().subsequent_indent("  "));
+        for image in &self.images[..self.visible_images()] {
+            self.images_list.push(names::normalize(&image.name));
+        }
+        if self.collapsed > 0 {
+            self.images_list.push(format!(
+                "… {} weaker matches (press x to expand)",
+                self.collapsed
+            ));
+        }
+
+        // The image that was selected when the category was last left
+        let remembered = match &self.list_source {
+            Some(ListSource::Category(category)) if !refreshed => {
+                self.category_selections.get(category).cloned()
+            },
+            _ => None,
+        };
+        if let Some(i) = selected
+            .or(remembered)
+            .and_then(|x| self.images.iter().position(|y| y.search_id == x))
+            .filter(|x| *x < self.visible_images())
+        {
+            self.images_list.state.select(Some(i));
+        } else if let Some(ListSource::Category(category)) = &self.list_source {
+            if self.session.category.as_ref() == Some(category) {
+                self.session.category = None;
+                if let Some(state) = self.session.images.take() {
+                    self.images_list.restore_state(state);
+                }
+            }
+        }
+
+        self.scan_downloads();
+    }
+
+    /// Keeps the shown category or search result list around to switch back
+    /// to.
+    fn put_aside_list(&mut self) {
+        let Some(source) = self.list_source.take() else {
+            return;
+        };
+        // NOTE: Expanded results are collapsed again by `install_list`, so only the
+        // ranked order is kept
+        if let (ListSource::Category(category), Some(image)) = (&source, self.selected_image()) {
+            self.category_selections
+                .insert(category.clone(), image.search_id.clone());
+        }
+        let saved = SavedList {
+            source,
+            images: self.source_images.clone(),
+            state: self.images_list.save_state(),
+        };
+        match saved.source {
+            ListSource::Category(_) => self.last_category_list = Some(saved),
+            ListSource::Search(..) => self.last_search_list = Some(saved),
+            ListSource::Tag(..) => {},
+        }
+    }
+
+    fn handle_cycle_sort(&mut self) {
+        self.sort_by = self.sort_by.next();
+        let Some(source) = self.list_source.clone() else {
+            return;
+        };
+        // Installed as a refresh to keep the selected image selected
+        self.install_list(source, self.source_images.clone(), true);
+    }
+
+    /// Puts everything changed with a key while browsing back the way it was
+    /// at startup: the sort order, the real size and preview modes, the
+    /// search scope, the pinned image and the marks for comparing. Options
+    /// from the config are left alone.
+    fn handle_reset_view(&mut self) {
+        self.native_size = false;
+        self.native_image = None;
+        self.show_preview = false;
+        self.preview = None;
+        self.session.search_all = false;
+        self.pinned = None;
+        self.marked.clear();
+        self.focus = Focus::List;
+        if self.sort_by != SortBy::default() {
+            self.sort_by = SortBy::default();
+            if let Some(source) = self.list_source.clone() {
+                // Installed as a refresh to keep the selected image selected
+                self.install_list(source, self.source_images.clone(), true);
+            }
+        }
+        self.toast = Some(Toast::new("View reset to the defaults"));
+    }
+
+    /// Switches between the last category list and the last search results
+    /// without fetching either again.
+    fn handle_switch_list(&mut self) {
+        let saved = match self.list_source {
+            Some(ListSource::Search(..)) => self.last_category_list.take(),
+            _ => self.last_search_list.take(),
+        };
+        let Some(saved) = saved else {
+            self.toast = Some(Toast::new(match self.list_source {
+                Some(ListSource::Search(..)) => "No category list to switch to",
+                _ => "No search results to switch to",
+            }));
+            return;
+        };
+
+        // A list still loading would replace the one switched to
+        self.loading_list = None;
+        self.running_state = if saved.images.is_empty() {
+            RunningState::EmptyResults
+        } else {
+            RunningState::BrowsingImages
+        };
+        self.install_list(saved.source, saved.images, false);
+        self.images_list.restore_state(saved.state);
+    }
+
+    /// Looks for the books of the categories in the image list that haven't
+    /// been scanned yet in the download directory.
+    fn scan_downloads(&mut self) {
+        let dirs = self
+            .images
+            .iter()
+            .map(|x| &x.category)
+            .filter(|x| !self.on_disk.contains_key(*x))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|x| (x.clone(), self.download_dir(x)))
+            .collect::<Vec<_>>();
+        if dirs.is_empty() {
+            return;
+        }
+
+        self.tasks.spawn(async move {
+            // NOTE: Without download_by_category every category shares a directory, so
+            // it's only read once
+            let mut names_in = HashMap::new();
+            let mut scanned = Vec::with_capacity(dirs.len());
+            for (category, dir) in dirs {
+                if !names_in.contains_key(&dir) {
+                    let names = downloaded_names(&dir).await;
+                    names_in.insert(dir.clone(), names);
+                }
+                scanned.push((category, names_in[&dir].clone()));
+            }
+            Ok(Message::DownloadsScanned(scanned))
+        });
+    }
+
+    /// Whether the book is in the download directory.
+    fn is_on_disk(&self, image: &BookData) -> bool {
+        self.on_disk
+            .get(&image.category)
+            .is_some_and(|x| x.contains(&names::normalize(&image.name)))
+    }
+
+    /// Selects the next book that isn't in the download directory, or the
+    /// previous one if `forward` is false.
+    fn handle_jump_not_downloaded(&mut self, forward: bool) {
+        let len = self.visible_images();
+        if len == 0 {
+            return;
+        }
+        let from = self
+            .images_list
+            .state
+            .selected()
+            .unwrap_or_default()
+            .min(len - 1);
+        let found = (1..len)
+            .map(|i| {
+                if forward {
+                    (from + i) % len
+                } else {
+                    (from + len - i) % len
+                }
+            })
+            .find(|i| !self.is_on_disk(&self.images[*i]));
+        match found {
+            Some(i) => self.images_list.state.select(Some(i)),
+            None => self.toast = Some(Toast::new("Every other book is downloaded")),
+        }
+    }
+
+    /// Scores search results against the query, and moves the ones below the
+    /// configured minimum to the end of the list to be collapsed.
+    fn rank_results(&mut self, source: &ListSource) {
+        self.collapsed = 0;
+        // NOTE: Every image matches an empty query equally, so there's nothing to rank
+        let Some(ListSource::Search(query, _)) = Some(source).filter(|x| !x.is_browse_all()) else {
+            self.relevance.clear();
+            self.sort_by.sort(&mut self.images, |x| x);
+            return;
+        };
+
+        let mut ranked = self
+            .images
+            .drain(..)
+            .map(|x| (fuzzy::relevance(query, &x.name), x))
+            .collect::<Vec<_>>();
+        // NOTE: Stable, so the API's order is kept on both sides
+        ranked.sort_by_key(|(relevance, _)| *relevance < self.config.min_relevance);
+        self.collapsed = ranked
+            .iter()
+            .filter(|(relevance, _)| *relevance < self.config.min_relevance)
+            .count();
+        // The weak matches stay collapsed at the end whatever the order
+        let strong_len = ranked.len() - self.collapsed;
+        let (strong, weak) = ranked.split_at_mut(strong_len);
+        self.sort_by.sort(strong, |(_, x)| x);
+        self.sort_by.sort(weak, |(_, x)| x);
+        (self.relevance, self.images) = ranked.into_iter().unzip();
+    }
+
+    fn handle_expand_results(&mut self) {
+        if self.collapsed == 0 {
+            return;
+        }
+        // Swap the collapsed row for the results it stood for
+        self.images_list.pop();
+        for image in &self.images[self.visible_images()..] {
+            self.images_list.push(names::normalize(&image.name));
+        }
+        self.collapsed = 0;
+    }
+
+    fn visible_images(&self) -> usize { self.images.len() - self.collapsed }
+
+    /// Returns the selected image, `None` when nothing or the collapsed results
+    /// row is selected.
+    /// Selects the next image, or the previous one if `forward` is false,
+    /// skipping the ones that failed to decode. Stays put after a full cycle
+    /// if every one of them did.
+    fn step_images(&mut self, forward: bool) {
+        let start = self.images_list.state.selected();
+        for _ in 0..self.images_list.len() {
+            if forward {
+                self.images_list.next();
+            } else {
+                self.images_list.previous();
+            }
+            if !self
+                .selected_image()
+                .is_some_and(|x| self.failed_images.contains_key(&x.search_id))
+            {
+                return;
+            }
+        }
+        self.images_list.state.select(start);
+    }
+
+    fn selected_image(&self) -> Option<&Arc<BookData>> {
+        self.images_list
+            .state
+            .selected()
+            .filter(|i| *i < self.visible_images())
+            .map(|i| &self.images[i])
+    }
+
+    fn handle_toggle_native_size(&mut self) {
+        let Some(image) = &self.image else {
+            return;
+        };
+        if image.protocol == ProtocolType::Halfblocks {
+            self.toast = Some(Toast::new(
+                "Halfblocks can't show images at their real size",
+            ));
+            return;
+        }
+
+        self.native_size = !self.native_size;
+        self.native_image = None;
+        self.spawn_native_image();
+    }
+
+    fn handle_pan_image(&mut self, x: i8, y: i8) {
+        if let Some(image) = &mut self.native_image {
+            image.pan(x, y);
+        }
+        // Both compared images move together, so the same spot lines up
+        for image in self.compared.iter_mut().flatten() {
+            image.pan(x, y);
+        }
+    }
+
+    fn handle_zoom_image(&mut self, direction: i8) {
+        for image in self.compared.iter_mut().flatten() {
+            image.zoom(direction);
+        }
+    }
+
+    /// Marks the selected image to be compared, or unmarks it if it already
+    /// is. Only two images can be marked at once.
+    fn handle_toggle_mark(&mut self) {
+        let Some(image) = self.selected_image().cloned() else {
+            return;
+        };
+        if let Some(i) = self
+            .marked
+            .iter()
+            .position(|x| x.search_id == image.search_id)
+        {
+            self.marked.remove(i);
+            return;
+        }
+        if self.marked.len() == 2 {
+            self.toast = Some(Toast::new(
+                "Only two images can be compared, unmark one first",
+            ));
+            return;
+        }
+
+        self.marked.push(image);
+        if self.marked.len() == 2 {
+            self.toast = Some(Toast::new("Press C to compare the marked images"));
+        }
+    }
+
+    /// Fetches and decodes both marked images to show them side by side.
+    fn handle_compare(&mut self) {
+        let [first, second] = self.marked.as_slice() else {
+            self.toast = Some(Toast::new("Mark two images with m to compare them"));
+            return;
+        };
+        if self.picker.get().protocol_type == ProtocolType::Halfblocks {
+            self.toast = Some(Toast::new(
+                "Halfblocks can't show images at their real size",
+            ));
+            return;
+        }
+
+        let ids = [first.search_id.clone(), second.search_id.clone()];
+        let picker = self.picker.clone();
+        let latency = self.latency.clone();
+        self.tasks.spawn(async move {
+            let client = http::client();
+            let (first, second) = tokio::join!(
+                latency.time(Kind::Api, "book", client.get_id(&ids[0])),
+                latency.time(Kind::Api, "book", client.get_id(&ids[1])),
+            );
+            let (first, second) = match (first, second) {
+                (Ok(first), Ok(second)) => (first, second),
+                (Err(e), _) | (_, Err(e)) => return Ok(Message::CompareLoaded(Err(e.to_string()))),
+            };
+
+            let images = tokio::task::spawn_blocking(move || {
+                Some([
+                    NativeImage::decode(first.details.search_id, &first.raw_bytes, picker.clone())?,
+                    NativeImage::decode(second.details.search_id, &second.raw_bytes, picker)?,
+                ])
+            })
+            .await?;
+            Ok(Message::CompareLoaded(
+                images.ok_or_else(|| "unable to decode the images".to_owned()),
+            ))
+        });
+
+        self.compared = None;
+        self.previous_running_state = self.running_state;
+        self.running_state = RunningState::Comparing;
+    }
+
+    fn handle_compare_loaded(&mut self, result: Result<[NativeImage; 2], String>) {
+        // Closed before the images came in
+        if self.running_state != RunningState::Comparing {
+            return;
+        }
+        match result {
+            Ok(images) => self.compared = Some(images),
+            Err(reason) => {
+                self.toast = Some(Toast::new(format!("Unable to compare: {reason}")));
+                self.running_state = self.previous_running_state;
+            },
+        }
+    }
+
+    fn handle_swap_compared(&mut self) {
+        self.marked.swap(0, 1);
+        if let Some(images) = &mut self.compared {
+            images.swap(0, 1);
+        }
+    }
+
+    /// Decodes the current image again to show it at its real size, if that's
+    /// the mode images are shown in.
+    fn spawn_native_image(&mut self) {
+        let Some(image) = self
+            .image
+            .as_ref()
+            .filter(|x| self.native_size && x.protocol != ProtocolType::Halfblocks)
+        else {
+            return;
+        };
+        let search_id = image.search_id.clone();
+        let data = image.data.clone();
+        let picker = self.picker.clone();
+        self.tasks.spawn(async move {
+            let image =
+                tokio::task::spawn_blocking(move || NativeImage::decode(search_id, &data, picker))
+                    .await?;
+            Ok(Message::NativeImageDecoded(image))
+        });
+    }
+
+    /// Returns the image after the selected one.
+    fn next_image(&self) -> Option<&Arc<BookData>> {
+        self.images_list
+            .state
+            .selected()
+            .map(|i| i + 1)
+            .filter(|i| *i < self.visible_images())
+            .map(|i| &self.images[i])
+    }
+
+    fn handle_toggle_preview(&mut self) {
+        self.show_preview = !self.show_preview;
+        self.toast = Some(Toast::new(if self.show_preview {
+            "Previewing the next image"
+        } else {
+            "Stopped previewing the next image"
+        }));
+        if self.show_preview {
+            self.load_preview();
+        } else {
+            // It's cheap to load again, no need to hold on to it
+            self.preview = None;
+        }
+    }
+
+    /// Loads the preview of the next image, unless it's already loaded.
+    fn load_preview(&mut self) {
+        if !self.show_preview || self.prefetching_paused() {
+            return;
+        }
+        let Some(next) = self.next_image().cloned() else {
+            return;
+        };
+        if self
+            .preview
+            .as_ref()
+            .is_some_and(|x| x.search_id == next.search_id)
+        {
+            return;
+        }
+        self.preview = None;
+        spawn_load_preview(
+            &mut self.tasks,
+            self.picker.clone(),
+            self.latency.clone(),
+            next,
+            u64::from(self.config.max_image_megapixels) * 1_000_000,
+        );
+    }
+
+    fn handle_force_reload_image_list(&mut self) -> Vec<Message> {
+        let Some(source) = self.list_source.clone() else {
+            return vec![];
+        };
+        if let ListSource::Category(category) = &source {
+            self.category_cache.remove(category);
+            self.toast = Some(Toast::new("Cache cleared"));
+        }
+        vec![Message::LoadList(source)]
+    }
+
+    /// Loads the selected image, `force` skips the size limit.
+    fn handle_load_image(&mut self, force: bool) {
+        // Nothing to load when nothing is selected
+        let Some(image_ref) = self.selected_image().cloned() else {
+            return;
+        };
+
+        if let Some(image) = self.image.take() {
+            self.previous_image = Some(image);
+        }
+        self.loading_image = true;
+
+        let max_pixels = if force {
+            None
+        } else {
+            Some(u64::from(self.config.max_image_megapixels) * 1_000_000)
+        };
+        self.confirming_image = None;
+        self.image_retry = Some(ImageRetry {
+            book: image_ref.clone(),
+            max_pixels,
+            attempt: 0,
+        });
+        spawn_load_image(
+            &mut self.tasks,
+            self.picker.clone(),
+            self.latency.clone(),
+            image_ref,
+            max_pixels,
+            self.config.confirm_above_bytes.filter(|_| !force),
+        );
+    }
+
+    fn handle_reload_image(&mut self) {
+        if let Some(image_ref) = self.image.as_ref().and_then(|x| x.book.clone()) {
+            self.previous_image = self.image.take();
+            self.loading_image = true;
+            self.reloading_image = true;
+            self.image_retry = Some(ImageRetry {
+                book: image_ref.clone(),
+                max_pixels: None,
+                attempt: 0,
+            });
+            // It's already been shown, so the size limit doesn't matter
+            spawn_load_image(
+                &mut self.tasks,
+                self.picker.clone(),
+                self.latency.clone(),
+                image_ref,
+                None,
+                None,
+            );
+        }
+    }
+
+    fn handle_show_image(&mut self, image: Image) {
+        // A newer load took its place, like the one of a list opened meanwhile
+        if image.book.is_some()
+            && self
+                .image_retry
+                .as_ref()
+                .is_some_and(|x| x.book.search_id != image.search_id)
+        {
+            return;
+        }
+        self.seen
+            .insert(image.search_id.clone())
+            .wrap_err("unable to mark the image as seen")
+            .unwrap();
+        if self
+            .image_shown_at
+            .is_some_and(|x| x.elapsed() < MIN_IMAGE_INTERVAL)
+        {
+            self.pending_image = Some(image);
+            return;
+        }
+        self.image_shown_at = Some(Instant::now());
+
+        self.failed_images.remove(&image.search_id);
+        self.image_retry = None;
+        self.overwrite_pending = None;
+        self.previous_image = None;
+        tui::set_terminal_title(&image.name);
+
+        // NOTE: The image is shown first, the accent follows once it's computed so it
+        // doesn't hold up the image
+        self.spawn_background(&image);
+        if self.config.dynamic_accent {
+            let search_id = image.search_id.clone();
+            let data = image.data.clone();
+            self.tasks.spawn(async move {
+                let color =
+                    tokio::task::spawn_blocking(move || accent::average_color(&data)).await?;
+                Ok(Message::AccentComputed(search_id, color))
+            });
+        }
+
+        self.image = Some(image);
+        self.loading_image = false;
+        self.load_preview();
+        self.spawn_native_image();
+
+        if self.reloading_image {
+            self.reloading_image = false;
+            self.toast = Some(Toast::new("Image reloaded"));
+        }
+    }
+
+    /// Whether loading ahead of time is held off while the terminal doesn't
+    /// have focus.
+    fn prefetching_paused(&self) -> bool {
+        self.paused && self.config.stop_prefetching_when_unfocused
+    }
+
+    /// Returns the message showing the image held back by
+    /// [`MIN_IMAGE_INTERVAL`], once it's over.
+    fn pending_image_due(&mut self) -> Option<Message> {
+        let shown_at = self.image_shown_at;
+        self.pending_image
+            .take_if(|_| shown_at.is_none_or(|x| x.elapsed() >= MIN_IMAGE_INTERVAL))
+            .map(Message::ShowImage)
+    }
+
+    /// Updates how many bytes were sent to the terminal in the last second.
+    fn record_sent(&mut self) {
+        let written = tui::bytes_written();
+        match self.sent_window {
+            Some((start, at)) if start.elapsed() >= Duration::from_secs(1) => {
+                self.sent_per_sec = written - at;
+                self.sent_window = Some((Instant::now(), written));
+            },
+            Some(_) => {},
+            None => self.sent_window = Some((Instant::now(), written)),
+        }
+    }
+
+    /// Names the selected category in the terminal window title.
+    fn announce_selected_category(&self) {
+        if let Some(category) = self
+            .categories
+            .state
+            .selected()
+            .and_then(|i| self.categories.get(i))
+        {
+            tui::set_terminal_title(category);
+        }
+    }
+
+    /// Computes what fills the image pane around the image, on the blocking
+    /// pool as blurring takes a while.
+    fn spawn_background(&mut self, image: &Image) {
+        let mode = match self.config.image_background {
+            // NOTE: A second halfblocks layer is too heavy to draw every frame, the dim
+            // fill is close enough
+            ImageBackground::Blur if image.protocol == ProtocolType::Halfblocks => {
+                ImageBackground::Dim
+            },
+            ImageBackground::None => return,
+            mode => mode,
+        };
+
+        let search_id = image.search_id.clone();
+        let data = image.data.clone();
+        // The blurred copy is stretched to the size of the image pane in pixels
+        let width = u32::from(self.image_area.width) * u32::from(image.font_size.0);
+        let height = u32::from(self.image_area.height) * u32::from(image.font_size.1);
+        let picker = self.picker.clone();
+        self.tasks.spawn(async move {
+            let background = tokio::task::spawn_blocking(move || match mode {
+                ImageBackground::Blur => background::blur(&data, width, height)
+                    .map(|x| Background::Blur(picker.new_resize_protocol(x))),
+                _ => accent::dimmed_color(&data).map(Background::Dim),
+            })
+            .await?;
+            Ok(Message::BackgroundComputed(search_id, background))
+        });
+    }
+
+    fn handle_image_fetch_failed(&mut self, search_id: String, reason: String, transient: bool) {
+        let retries = self.config.image_load_retries;
+        let Some(retry) = self.image_retry_for(&search_id) else {
+            return;
+        };
+        if !transient || retry.attempt >= retries {
+            self.image_retry = None;
+            self.handle_image_decode_failed(search_id, reason);
+            return;
+        }
+
+        retry.attempt += 1;
+        // Give the connection a bit longer to come back after every attempt
+        let delay = Duration::from_secs(u64::from(retry.attempt));
+        self.tasks.spawn(async move {
+            tokio::time::sleep(delay).await;
+            Ok(Message::RetryImageLoad(search_id))
+        });
+    }
+
+    fn handle_confirm_image_load(&mut self, search_id: &str, bytes: u64) {
+        if self.image_retry_for(search_id).is_some() {
+            self.loading_image = false;
+            self.confirming_image = Some(bytes);
+        }
+    }
+
+    fn handle_accept_image_load(&mut self) {
+        let Some(retry) = &self.image_retry else {
+            return;
+        };
+        self.confirming_image = None;
+        self.loading_image = true;
+        spawn_load_image(
+            &mut self.tasks,
+            self.picker.clone(),
+            self.latency.clone(),
+            retry.book.clone(),
+            retry.max_pixels,
+            None,
+        );
+    }
+
+    /// Gives up on the image waiting to be confirmed, if there's one.
+    fn skip_image_load(&mut self) {
+        if self.confirming_image.take().is_some() {
+            self.image_retry = None;
+            self.image = self.previous_image.take();
+        }
+    }
+
+    fn handle_retry_image_load(&mut self, search_id: &str) {
+        if let Some(retry) = self.image_retry_for(search_id) {
+            let (book, max_pixels) = (retry.book.clone(), retry.max_pixels);
+            spawn_load_image(
+                &mut self.tasks,
+                self.picker.clone(),
+                self.latency.clone(),
+                book,
+                max_pixels,
+                None,
+            );
+        }
+    }
+
+    /// Returns the retry state of the image load with the identifier, or
+    /// `None` if it's no longer wanted. Moving to another row cancels the
+    /// load.
+    fn image_retry_for(&mut self, search_id: &str) -> Option<&mut ImageRetry> {
+        // A newer load took its place, which has its own retries
+        if self
+            .image_retry
+            .as_ref()
+            .is_none_or(|x| x.book.search_id != search_id)
+        {
+            return None;
+        }
+
+        if self.selected_image().map(|x| x.search_id.as_str()) != Some(search_id) {
+            self.image_retry = None;
+            self.confirming_image = None;
+            self.reloading_image = false;
+            self.loading_image = false;
+            self.image = self.previous_image.take();
+            return None;
+        }
+        self.image_retry.as_mut()
+    }
+
+    fn handle_image_decode_failed(&mut self, search_id: String, reason: String) {
+        self.toast = Some(Toast::new(format!("Unable to show the image: {reason}")));
+        self.failed_images.insert(search_id, reason);
+        self.reloading_image = false;
+        self.loading_image = false;
+        self.image = self.previous_image.take();
+    }
+
+    fn handle_toggle_favorite(&mut self) {
+        if let Some(image) = self.selected_image().cloned() {
+            let favorited = self
+                .favorites
+                .toggle(&image.search_id, &names::normalize(&image.name))
+                .wrap_err("unable to update the favorites")
+                .unwrap();
+            let favorite = Favorite {
+                search_id: image.search_id.clone(),
+                name: names::normalize(&image.name),
+            };
+            self.last_undoable_action = Some(if favorited {
+                UndoableAction::Favorited(favorite)
+            } else {
+                UndoableAction::Unfavorited(favorite)
+            });
+            self.toast = Some(Toast::new(if favorited {
+                "Added to favorites ⭐"
+            } else {
+                "Removed from favorites"
+            }));
+        }
+    }
+
+    fn handle_undo(&mut self) {
+        let Some(action) = self.last_undoable_action.take() else {
+            self.toast = Some(Toast::new("Nothing to undo"));
+            return;
+        };
+        match action {
+            UndoableAction::Favorited(favorite) | UndoableAction::Unfavorited(favorite) => {
+                self.favorites
+                    .toggle(&favorite.search_id, &favorite.name)
+                    .wrap_err("unable to update the favorites")
+                    .unwrap();
+            },
+            // NOTE: Trashed rather than deleted, so undoing by accident is recoverable too
+            UndoableAction::Downloaded {
+                path,
+                name,
+                category,
+            } => {
+                if let Err(e) = trash::delete(&path) {
+                    self.toast = Some(Toast::new(format!(
+                        "Unable to move the download to the trash: {e}"
+                    )));
+                    return;
+                }
+                if let Some(names) = self.on_disk.get_mut(&category) {
+                    names.remove(&name);
+                }
+                self.downloaded.remove(&(name, category));
+            },
+        }
+        self.toast = Some(Toast::new("Undone"));
+    }
+
+    fn handle_toggle_favorites(&mut self) {
+        if self.running_state != RunningState::ShowingFavorites && self.favorites.is_empty() {
+            self.toast = Some(Toast::new("No favorites yet, press f to add one"));
+            return;
+        }
+        self.favorites_list =
+            StatefulList::with_items(self.favorites.names().map(ToOwned::to_owned).collect());
+        self.favorite_moved_at = None;
+        self.toggle_popup(RunningState::ShowingFavorites);
+    }
+
+    /// Hides the selected image, selecting the one that takes its place.
+    fn handle_hide_image(&mut self) -> Vec<Message> {
+        let (Some(i), Some(image), Some(source)) = (
+            self.images_list.state.selected(),
+            self.selected_image().cloned(),
+            self.list_source.clone(),
+        ) else {
+            return vec![];
+        };
+        self.blacklist
+            .hide(&image.search_id, &names::normalize(&image.name))
+            .wrap_err("unable to update the blacklist")
+            .unwrap();
+        self.install_list(source, self.source_images.clone(), true);
+        self.toast = Some(Toast::new("Hidden, press B to see the hidden images"));
+
+        if self.images.is_empty() {
+            self.running_state = RunningState::EmptyResults;
+            return vec![];
+        }
+        self.images_list
+            .state
+            .select(Some(i.min(self.visible_images().saturating_sub(1))));
+        vec![]
+    }
+
+    fn handle_toggle_blacklist(&mut self) {
+        if self.running_state != RunningState::ShowingBlacklist && self.blacklist.is_empty() {
+            self.toast = Some(Toast::new("No hidden images, press H to hide one"));
+            return;
+        }
+        self.blacklist_list =
+            StatefulList::with_items(self.blacklist.names().map(ToOwned::to_owned).collect());
+        self.toggle_popup(RunningState::ShowingBlacklist);
+    }
+
+    /// Brings the selected hidden image back into the lists.
+    fn handle_unhide_image(&mut self) {
+        let Some(i) = self.blacklist_list.state.selected() else {
+            return;
+        };
+        let hidden = self
+            .blacklist
+            .unhide(i)
+            .wrap_err("unable to update the blacklist")
+            .unwrap();
+        self.blacklist_list.remove(i);
+        let len = self.blacklist_list.len();
+        self.blacklist_list
+            .state
+            .select((len > 0).then(|| i.min(len - 1)));
+        self.toast = Some(Toast::new(format!(
+            "\"{}\" is back in the lists",
+            hidden.name
+        )));
+
+        if let Some(source) = self.list_source.clone() {
+            self.install_list(source, self.source_images.clone(), true);
+        }
+        if self.blacklist.is_empty() {
+            self.toggle_popup(RunningState::ShowingBlacklist);
+        }
+    }
+
+    /// Swaps the selected favorite with the one below it, or above it if `down`
+    /// is false. The selection follows the favorite.
+    fn handle_move_favorite(&mut self, down: bool) {
+        let Some(i) = self.favorites_list.state.selected() else {
+            return;
+        };
+        let j = if down { i + 1 } else { i.wrapping_sub(1) };
+        if j >= self.favorites_list.len() {
+            return;
+        }
+
+        self.favorites
+            .swap(i, j)
+            .wrap_err("unable to reorder the favorites")
+            .unwrap();
+        self.favorites_list.swap(i, j);
+        self.favorites_list.state.select(Some(j));
+        self.favorite_moved_at = Some(Instant::now());
+    }
+
+    fn handle_export_bookmarks(&mut self) {
+        if self.favorites.is_empty() {
+            self.toast = Some(Toast::new("No favorites to export"));
+            return;
+        }
+
+        let path = self
+            .favorites
+            .export_script(&http::client().api_url)
+            .wrap_err("unable to export the favorites")
+            .unwrap();
+        self.toast = Some(Toast::new(format!("Exported to {}", path.display())));
+    }
+
+    fn handle_export_list(&mut self) {
+        if let Some(source) = &self.list_source {
+            let message = match export::write_csv(source, &self.images, &http::client().api_url) {
+                Ok(path) => format!("Exported to {}", path.display()),
+                Err(e) => format!("Unable to export the list: {e}"),
+            };
+            self.toast = Some(Toast::new(message));
+        }
+    }
+
+    fn handle_clear_seen(&mut self) {
+        self.seen
+            .remove_all(self.images.iter().map(|x| x.search_id.as_str()))
+            .wrap_err("unable to clear the seen images")
+            .unwrap();
+    }
+
+    /// Returns the image downloading and copying act on.
+    fn focused_image(&self) -> Option<&Image> {
+        if self.focus == Focus::Pinned {
+            self.pinned.as_ref()
+        } else {
+            self.image.as_ref()
+        }
+    }
+
+    fn handle_edit_note(&mut self) {
+        let Some(image) = &self.image else {
+            return;
+        };
+
+        let note = self.notes.get(&image.name).unwrap_or_default();
+        self.note_editor = TextArea::new(note.lines().map(str::to_owned).collect());
+        self.note_editor.set_block(
+            Block::bordered()
+                .border_type(self.config.border_style.popup_blocks.into())
+                .title(format!(" Note: {} ", image.name)),
+        );
+        self.previous_running_state = self.running_state;
+        self.running_state = RunningState::EditingNote;
+    }
+
+    fn handle_save_note(&mut self) {
+        if let Some(image) = &self.image {
+            self.notes
+                .set(&image.name, self.note_editor.lines().join("\n"))
+                .wrap_err("unable to save the note")
+                .unwrap();
+            self.toast = Some(Toast::new("Note saved"));
+        }
+        self.running_state = self.previous_running_state;
+    }
+
+    /// Moves the focus to the next pane, skipping the empty image panes.
+    fn handle_switch_focus(&mut self) {
+        self.focus = match self.focus {
+            Focus::List if self.image.is_some() => Focus::Image,
+            Focus::List | Focus::Image if self.pinned.is_some() => Focus::Pinned,
+            _ => Focus::List,
+        };
+    }
+
+    fn load_selected_image(&self) -> Vec<Message> {
+        if self.selected_image().is_some() {
+            vec![Message::LoadImage]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn handle_toggle_pin(&mut self) {
+        if self.pinned.take().is_some() {
+            if self.focus == Focus::Pinned {
+                self.focus = Focus::Image;
+            }
+            return;
+        }
+        let Some(image) = &self.image else {
+            return;
+        };
+
+        // NOTE: The protocol state can't be shared between two widgets, so the pinned
+        // copy is decoded again from the same data
+        let book = image.book.clone();
+        let name = image.name.clone();
+        let search_id = image.search_id.clone();
+        let category = image.category.clone();
+        let data = image.data.clone();
+        let picker = self.picker.clone();
+        self.tasks.spawn(async move {
+            let image = new_image(&picker, book, &name, search_id, category, data, None)
+                .wrap_err("unable to decode the image to pin")?;
+            Ok(Message::PinImage(image))
+        });
+    }
+
+    fn handle_copy_data_uri(&mut self) {
+        let Some(image) = self.focused_image() else {
+            return;
+        };
+
+        // NOTE: The image already decoded fine, so its format is known
+        let mime = image::guess_format(&image.data)
+            .map_or("application/octet-stream", |x| x.to_mime_type());
+        let uri = format!("data:{mime};base64,{}", STANDARD.encode(&image.data));
+
+        self.toast = Some(Toast::new(match clipboard::copy(&uri) {
+            Err(e) => format!("Unable to copy the data URI: {e}"),
+            Ok(()) if uri.len() > LARGE_DATA_URI_BYTES => format!(
+                "Copied a {} MiB data URI, it might be too large to paste",
+                uri.len() / (1024 * 1024),
+            ),
+            Ok(()) => "Copied the data URI".to_owned(),
+        }));
+    }
+
+    /// Writes the image to a temp file, removed when the app quits, and copies
+    /// its path to open it in another program.
+    fn handle_copy_to_temp_file(&mut self) {
+        let Some(image) = self.focused_image() else {
+            return;
+        };
+
+        // NOTE: The image already decoded fine, so its format is known
+        let extension = image::guess_format(&image.data)
+            .ok()
+            .and_then(|x| x.extensions_str().first().copied())
+            .unwrap_or("bin");
+        let written = tempfile::Builder::new()
+            .prefix("aghpb-")
+            .suffix(&format!(".{extension}"))
+            .tempfile()
+            .and_then(|mut file| {
+                file.write_all(&image.data)?;
+                Ok(file.into_temp_path())
+            });
+        let path = match written {
+            Ok(path) => path,
+            Err(e) => {
+                self.toast = Some(Toast::new(format!("Unable to write the temp file: {e}")));
+                return;
+            },
+        };
+
+        let shown = path.display().to_string();
+        self.toast = Some(Toast::new(match clipboard::copy(&shown) {
+            Ok(()) => format!("Copied the path: {shown}"),
+            Err(e) => format!("Saved to {shown}, unable to copy the path: {e}"),
+        }));
+        self.temp_files.push(path);
+    }
+
+    fn handle_copy_state(&mut self) {
+        let selected = |list: &StatefulList| {
+            list.state
+                .selected()
+                .map(|i| json!({ "index": i, "item": list.get(i) }))
+        };
+        let Picker {
+            protocol_type,
+            font_size,
+            is_tmux,
+            ..
+        } = self.picker.get();
+        // NOTE: Only what's needed to retrace the user's steps, the image data would
+        // dwarf everything else
+        let state = json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "running_state": format!("{:?}", self.running_state),
+            "previous_running_state": format!("{:?}", self.previous_running_state),
+            "focus": format!("{:?}", self.focus),
+            "category": selected(&self.categories),
+            "list_source": self.list_source.as_ref().map(|x| format!("{x:?}")),
+            "image_list": selected(&self.images_list),
+            "images": self.images.len(),
+            "collapsed": self.collapsed,
+            "sort_by": format!("{:?}", self.sort_by),
+            "search_query": self.search_input.value(),
+            "search_category": self.search_category,
+            "image": self.image.as_ref().map(|x| &x.search_id),
+            "pinned": self.pinned.as_ref().map(|x| &x.search_id),
+            "loading_image": self.loading_image,
+            "protocol": format!("{protocol_type:?}"),
+            "font_size": font_size,
+            "is_tmux": is_tmux,
+        });
+
+        self.toast = Some(Toast::new(
+            match clipboard::copy(&serde_json::to_string_pretty(&state).unwrap_or_default()) {
+                Ok(()) => "Copied the app state".to_owned(),
+                Err(e) => format!("Unable to copy the app state: {e}"),
+            },
+        ));
+    }
+
+    async fn handle_download_image(&mut self) {
+        let Some(image) = self.focused_image() else {
+            unreachable!("no image to download")
+        };
+        let download_path = self.download_path(image);
+        let (name, category, search_id, data) = (
+            image.name.clone(),
+            image.category.clone(),
+            image.search_id.clone(),
+            image.data.clone(),
+        );
+
+        let already_downloaded = self.downloaded.contains(&(name.clone(), category.clone()))
+            || fs::metadata(&download_path)
+                .await
+                .is_ok_and(|x| x.len() == data.len() as u64);
+
+        // Ask before overwriting, the second press goes through
+        if already_downloaded && self.overwrite_pending.as_ref() != Some(&search_id) {
+            self.overwrite_pending = Some(search_id);
+            self.toast = Some(Toast::new(
+                "Already downloaded — press d again to overwrite",
+            ));
+            return;
+        }
+        self.overwrite_pending = None;
+
+        let permits = self.downloads.permits.clone();
+        let download = self.downloads.push(name, category, download_path, data);
+        spawn_download(&mut self.tasks, &permits, download);
+    }
+
+    /// Saves a copy of the image scaled down to each of the configured sizes,
+    /// next to where `d` downloads it. Every size is a download of its own, so
+    /// one failing doesn't hold back the others.
+    fn handle_download_thumbnails(&mut self) {
+        let Some(image) = self.focused_image() else {
+            unreachable!("no image to download")
+        };
+        if self.config.download_sizes.is_empty() {
+            self.toast = Some(Toast::new(
+                "Set `download_sizes` in the config to save resized copies",
+            ));
+            return;
+        }
+
+        let download_path = self.download_path(image);
+        let (name, category, data) = (
+            image.name.clone(),
+            image.category.clone(),
+            image.data.clone(),
+        );
+        let permits = self.downloads.permits.clone();
+        for &size in &self.config.download_sizes {
+            let mut path = download_path.clone();
+            path.set_file_name(format!("{name}-{size}px.jpeg"));
+            let download = self
+                .downloads
+                .push(name.clone(), category.clone(), path, data.clone());
+            download.thumbnail = Some(size);
+            spawn_download(&mut self.tasks, &permits, download);
+        }
+    }
+
+    fn download_path(&self, image: &Image) -> PathBuf {
+        let mut path = self.download_dir(&image.category);
+        path.push(format!("{}.jpeg", image.name));
+        path
+    }
+
+    /// Returns the directory the books of the category are downloaded to.
+    fn download_dir(&self, category: &str) -> PathBuf {
+        let mut path = dirs::download_dir()
+            .wrap_err("unable to locate download directory")
+            .unwrap();
+        if self.config.download_by_category {
+            path.push("aghpb");
+            path.push(category);
+        }
+        path
+    }
+
+    fn handle_download_finished(&mut self, id: usize, result: Result<(), String>) {
+        let succeeded = result.is_ok();
+        if self.config.bell {
+            // NOTE: The bell is only a cue, not being able to ring it is fine
+            let _ = toast::bell();
+        }
+
+        // Resized copies only report how they went, the book itself isn't any
+        // more downloaded for them
+        if let Some(download) = self.downloads.get_mut(id).filter(|x| x.thumbnail.is_some()) {
+            let size = download.thumbnail.unwrap_or_default();
+            self.toast = Some(Toast::new(match &result {
+                Ok(()) => format!("Saved the {size}px copy of {}", download.name),
+                Err(reason) => format!("Saving the {size}px copy failed: {reason}"),
+            }));
+            download.result = Some(result);
+            return;
+        }
+
+        if let Err(reason) = &result {
+            self.toast = Some(Toast::new(format!("Download failed: {reason}")));
+        }
+        if let Some(download) = self.downloads.get_mut(id) {
+            if succeeded {
+                self.downloaded
+                    .insert((download.name.clone(), download.category.clone()));
+                self.on_disk
+                    .entry(download.category.clone())
+                    .or_default()
+                    .insert(download.name.clone());
+                self.last_undoable_action = Some(UndoableAction::Downloaded {
+                    path: download.path.clone(),
+                    name: download.name.clone(),
+                    category: download.category.clone(),
+                });
+                self.finished_download = Some((download.path.clone(), download.data.len()));
+            }
+            download.result = Some(result);
+        }
+
+        if succeeded
+            && matches!(
+                self.running_state,
+                RunningState::BrowsingCategories | RunningState::BrowsingImages
+            )
+        {
+            self.previous_running_state = self.running_state;
+            self.running_state = RunningState::ShowingDownloadPopup;
+            self.download_popup_opened_at = Some(Instant::now());
+        }
+    }
+
+    /// Returns the message closing a popup that's been open for
+    /// `popup_timeout_secs`.
+    fn popup_dismiss_due(&self) -> Option<Message> {
+        let timeout = Duration::from_secs(self.config.popup_timeout_secs?);
+        let due = |opened_at: Option<Instant>| opened_at.is_some_and(|x| x.elapsed() >= timeout);
+        if self.api_warning.is_some() && due(self.api_warning_opened_at) {
+            Some(Message::DismissApiWarning)
+        } else {
+            (self.running_state == RunningState::ShowingDownloadPopup
+                && due(self.download_popup_opened_at))
+            .then_some(Message::DismissDownloadPrompt)
+        }
+    }
+
+    /// Returns the message starting the screensaver or moving it on to the
+    /// next book, when it's time to. The screensaver doesn't start over a
+    /// popup or while anything is loading or downloading.
+    fn screensaver_due(&self) -> Option<Message> {
+        if self.paused {
+            return None;
+        }
+        if let Some(screensaver) = &self.screensaver {
+            return screensaver
+                .next_at
+                .filter(|x| *x <= Instant::now())
+                .map(|_| Message::NextScreensaverImage);
+        }
+
+        let idle = Duration::from_secs(self.config.screensaver_minutes? * 60);
+        let browsing = matches!(
+            self.running_state,
+            RunningState::BrowsingCategories
+                | RunningState::BrowsingImages
+                | RunningState::BrowsingByTag
+                | RunningState::EmptyResults
+        );
+        let (downloading, ..) = self.downloads.summary();
+        (self.last_input?.elapsed() >= idle
+            && browsing
+            && downloading == 0
+            && self.loading_list.is_none()
+            && !self.loading_image)
+            .then_some(Message::StartScreensaver)
+    }
+
+    fn spawn_screensaver_image(&mut self) {
+        let Some(screensaver) = &mut self.screensaver else {
+            return;
+        };
+        screensaver.next_at = None;
+
+        let picker = self.picker.clone();
+        let latency = self.latency.clone();
+        let max_pixels = u64::from(self.config.max_image_megapixels) * 1_000_000;
+        // NOTE: The books are kept away from the seen marks and the caches, the user
+        // didn't pick them
+        self.tasks.spawn(async move {
+            let Ok(book) = latency
+                .time(Kind::Api, "random", http::client().random())
+                .await
+            else {
+                return Ok(Message::ScreensaverImage(None));
+            };
+            let image = new_image(
+                &picker,
+                None,
+                &book.details.name,
+                book.details.search_id,
+                book.details.category,
+                book.raw_bytes,
+                Some(max_pixels),
+            );
+            Ok(Message::ScreensaverImage(image.ok()))
+        });
+    }
+
+    fn handle_screensaver_image(&mut self, image: Option<Image>) {
+        // The screensaver was stopped while the book loaded
+        let Some(screensaver) = &mut self.screensaver else {
+            return;
+        };
+        // A book that failed to load is skipped, the current one stays up until the
+        // next
+        if image.is_some() {
+            screensaver.image = image;
+        }
+        screensaver.next_at =
+            Some(Instant::now() + Duration::from_secs(self.config.screensaver_interval_secs));
+    }
+
+    /// Built for both drawing the popup and routing keys to it. `None` without
+    /// a warning to show.
+    fn api_warning_popup(&self) -> Option<Popup<'_>> {
+        let version = self.api_warning?;
+        Some(
+            Popup::new(
+                " Warning ",
+                vec![
+                    "API version mismatch. Some features may not work.".into(),
+                    format!("The API is at {version}, the app knows {SUPPORTED_API_MAJOR}.x")
+                        .into(),
+                ],
+            )
+            .fallback("Dismiss", Message::DismissApiWarning)
+            .border_type(self.config.border_style.popup_blocks.into()),
+        )
+    }
+
+    /// Built for both drawing the popup and routing keys to it.
+    fn download_popup(&self) -> Popup<'_> {
+        let (path, size) = self
+            .finished_download
+            .as_ref()
+            .map(|(path, size)| (path.display().to_string(), format_size(*size)))
+            .unwrap_or_default();
+
+        Popup::new(
+            "",
+            vec!["Download successful!".into(), path.into(), size.into()],
+        )
+        .action(
+            KeyCode::Char('o'),
+            "Open folder",
+            Message::OpenDownloadFolder,
+        )
+        .action(KeyCode::Char('c'), "Copy path", Message::CopyDownloadPath)
+        .fallback("Dismiss", Message::DismissDownloadPrompt)
+        .border_type(self.config.border_style.popup_blocks.into())
+    }
+
+    fn handle_open_download_folder(&mut self) {
+        let Some(dir) = self
+            .finished_download
+            .as_ref()
+            .and_then(|(path, _)| path.parent())
+        else {
+            return;
+        };
+        if let Err(e) = file_manager::open(dir) {
+            self.toast = Some(Toast::new(format!("Unable to open the folder: {e}")));
+        }
+        self.running_state = self.previous_running_state;
+    }
+
+    fn handle_copy_download_path(&mut self) {
+        let Some((path, _)) = &self.finished_download else {
+            return;
+        };
+        self.toast = Some(Toast::new(
+            match clipboard::copy(&path.display().to_string()) {
+                Ok(()) => "Copied the path".to_owned(),
+                Err(e) => format!("Unable to copy the path: {e}"),
+            },
+        ));
+        self.running_state = self.previous_running_state;
+    }
+
+    fn handle_move_up_help(&mut self) {
+        let i = self.help_state.selected().unwrap_or_default();
+        self.help_state.select(Some(i.saturating_sub(1)));
+    }
+
+    fn handle_move_down_help(&mut self) {
+        let i = self.help_state.selected().unwrap_or_default();
+        self.help_state
+            .select(Some((i + 1).min(help::HELP_TABLE.len() - 1)));
+    }
+
+    fn handle_open_category_palette(&mut self) {
+        self.previous_running_state = self.running_state;
+        self.running_state = RunningState::ShowingCategoryPalette;
+        self.category_palette_input.reset();
+        self.category_palette =
+            StatefulList::with_items(self.categories.iter().map(str::to_owned).collect());
+    }
+
+    /// Returns the state of every list drawn.
+    fn list_states(&mut self) -> [&mut ListState; 8] {
+        [
+            &mut self.categories.state,
+            &mut self.tags_list.state,
+            &mut self.images_list.state,
+            &mut self.suggestions.state,
+            &mut self.category_palette.state,
+            &mut self.favorites_list.state,
+            &mut self.blacklist_list.state,
+            &mut self.downloads.state,
+        ]
+    }
+
+    /// Returns the list being browsed under the popups.
+    fn browsed_list(&mut self) -> &mut StatefulList {
+        if self.previous_running_state == RunningState::BrowsingCategories {
+            &mut self.categories
+        } else {
+            &mut self.images_list
+        }
+    }
+
+    fn handle_open_find(&mut self) {
+        self.previous_running_state = self.running_state;
+        self.running_state = RunningState::FindingInList;
+        self.find_input.reset();
+        self.find_origin = self.browsed_list().state.selected();
+    }
+
+    fn handle_find_input(&mut self, key: KeyEvent) {
+        if !self
+            .find_input
+            .handle_event(&Event::Key(key))
+            .is_some_and(|x| x.value)
+        {
+            return;
+        }
+
+        let query = self.find_input.value().to_owned();
+        let origin = self.find_origin;
+        let list = self.browsed_list();
+        if query.is_empty() {
+            list.state.select(origin);
+            return;
+        }
+        // Start at the original selection itself, so it stays selected while it
+        // matches
+        let from = (origin.unwrap_or_default() + list.len()).saturating_sub(1);
+        if let Some(i) = next_find_match(list, &query, from, true) {
+            list.state.select(Some(i));
+        }
+    }
+
+    /// Selects the next item matching the find input, or the previous one if
+    /// `forward` is false.
+    fn handle_find(&mut self, forward: bool) {
+        let query = self.find_input.value().to_owned();
+        if query.is_empty() {
+            return;
+        }
+        let list = self.browsed_list();
+        let from = list.state.selected().unwrap_or_default();
+        let found = next_find_match(list, &query, from, forward);
+        if found.is_some() {
+            list.state.select(found);
+        }
+    }
+
+    fn handle_cancel_find(&mut self) {
+        let origin = self.find_origin;
+        self.browsed_list().state.select(origin);
+        self.running_state = self.previous_running_state;
+    }
+
+    fn handle_category_palette_input(&mut self, key: KeyEvent) {
+        if self
+            .category_palette_input
+            .handle_event(&Event::Key(key))
+            .is_some_and(|x| x.value)
+        {
+            self.category_palette = StatefulList::with_items(fuzzy::filter(
+                self.category_palette_input.value(),
+                self.categories.iter(),
+            ));
+            if self.category_palette.is_empty() {
+                self.category_palette.deselect();
+            }
+        }
+    }
+
+    fn handle_retry_download(&mut self) {
+        let permits = self.downloads.permits.clone();
+        if let Some(download) = self.downloads.selected_mut() {
+            if download.result.as_ref().is_some_and(Result::is_err) {
+                download.reset();
+                spawn_download(&mut self.tasks, &permits, download);
+            }
+        }
+    }
+
+    fn handle_cancel_download(&mut self) {
+        if let Some(download) = self.downloads.remove_selected() {
+            download.cancel();
+        }
+    }
+}
+
+/// Returns the letters Ctrl jumps to, with the letter of the selected
+/// category highlighted and the ones no category starts with dimmed.
+fn alphabet_bar(categories: &StatefulList, accent: Color) -> Line<'static> {
+    let initial = |x: &str| x.chars().next().map(|x| x.to_ascii_uppercase());
+    let selected = categories
+        .state
+        .selected()
+        .and_then(|x| categories.get(x))
+        .and_then(initial);
+    let initials = categories
+        .iter()
+        .filter_map(initial)
+        .collect::<HashSet<_>>();
+
+    let spans = ('A'..='Z')
+        .flat_map(|letter| {
+            let span = Span::from(letter.to_string());
+            let span = if selected == Some(letter) {
+                span.bold().reversed().fg(accent)
+            } else if initials.contains(&letter) {
+                span
+            } else {
+                span.dim()
+            };
+            [" ".into(), span]
+        })
+        // Skip the space before `A`
+        .skip(1)
+        .collect::<Vec<_>>();
+    Line::from(spans).centered()
+}
+
+/// Renders the image in the middle of the area, keeping its aspect ratio.
+fn render_image(
+    f: &mut Frame,
+    image: &mut Image,
+    area: Rect,
+    aspect_correction_override: Option<f32>,
+) {
+    let protocol = image.protocol;
+    // Kitty and iTerm2 place images pixel accurately, so the image is fit to the
+    // real size of the cells and scaled once with a smooth filter. The other
+    // protocols quantize the image anyway and stay as they were.
+    let pixel_accurate = matches!(protocol, ProtocolType::Kitty | ProtocolType::Iterm2);
+    // NOTE: Terminal cells are about twice as tall as they are wide
+    let aspect_correction = aspect_correction_override.unwrap_or(match protocol {
+        ProtocolType::Halfblocks => 2.0,
+        _ if pixel_accurate && image.font_size.0 > 0 => {
+            f32::from(image.font_size.1) / f32::from(image.font_size.0)
+        },
+        _ => 2.15,
+    });
+
+    // HACK: Halfblocks doesn't work with fractional scailing
+    let image_layout = if image.width > image.height {
+        let height = if protocol == ProtocolType::Halfblocks {
+            (f32::from(area.width) * (f32::from(image.height) / f32::from(image.width))
+                / aspect_correction)
+                .ceil() as u16
+        } else {
+            (f32::from(area.width) * (f32::from(image.height) / f32::from(image.width))
+                / aspect_correction)
+                .floor() as u16
+        };
+
+        Layout::vertical([Constraint::Length(height)])
+            .flex(Flex::Center)
+            .split(area)
+    } else {
+        let width = (f32::from(area.height)
+            * (f32::from(image.width) / f32::from(image.height))
+            * aspect_correction)
+            .ceil() as u16;
+
+        Layout::horizontal([Constraint::Length(width)])
+            .flex(Flex::Center)
+            .split(area)
+    };
+
+    let resize = if pixel_accurate {
+        Resize::Fit(Some(FilterType::CatmullRom))
+    } else {
+        Resize::Fit(None)
+    };
+    // NOTE: The protocol encodes the image on the first draw, which is what makes
+    // it slow
+    let start = Instant::now();
+    f.render_stateful_widget(
+        StatefulImage::new(None).resize(resize),
+        image_layout[0],
+        &mut image.state,
+    );
+    image.timings.render.get_or_insert(start.elapsed());
+}
+
+fn spawn_load_list(tasks: &mut JoinSet<Result<Message>>, latency: Latency, source: ListSource) {
+    tasks.spawn(async move {
+        Ok(
+            match latency.time(Kind::Api, "list", source.fetch()).await {
+                Ok(images) => Message::ListLoaded(source, images),
+                Err(e) => Message::ListLoadFailed(source, format!("{e:#}")),
+            },
+        )
+    });
+}
+
+/// Loads the preview of the next image. Without a preview there's just nothing
+/// in the corner, so failures aren't reported.
+fn spawn_load_preview(
+    tasks: &mut JoinSet<Result<Message>>,
+    picker: SharedPicker,
+    latency: Latency,
+    image_ref: Arc<BookData>,
+    max_pixels: u64,
+) {
+    tasks.spawn(async move {
+        let Ok(book_data) = latency
+            .time(
+                Kind::Api,
+                "preview",
+                http::client().get_id(&image_ref.search_id),
+            )
+            .await
+        else {
+            return Ok(Message::PreviewLoaded(None));
+        };
+        let image = new_image(
+            &picker,
+            Some(image_ref),
+            &book_data.details.name,
+            book_data.details.search_id,
+            book_data.details.category,
+            book_data.raw_bytes,
+            Some(max_pixels),
+        );
+        Ok(Message::PreviewLoaded(image.ok()))
+    });
+}
+
+fn spawn_load_image(
+    tasks: &mut JoinSet<Result<Message>>,
+    picker: SharedPicker,
+    latency: Latency,
+    image_ref: Arc<BookData>,
+    max_pixels: Option<u64>,
+    confirm_above: Option<u64>,
+) {
+    tasks.spawn(async move {
+        if let Some(limit) = confirm_above {
+            let size = latency
+                .time(
+                    Kind::Api,
+                    "size",
+                    http::client().book_size(&image_ref.search_id),
+                )
+                .await;
+            // NOTE: An unknown size isn't worth holding the image up over
+            if let Some(bytes) = size.filter(|x| *x > limit) {
+                return Ok(Message::ConfirmImageLoad {
+                    search_id: image_ref.search_id.clone(),
+                    bytes,
+                });
+            }
+        }
+
+        // Asynchronously fetch the book data
+        let start = Instant::now();
+        let book_data = match latency
+            .time(
+                Kind::Api,
+                "book",
+                http::client().get_id(&image_ref.search_id),
+            )
+            .await
+        {
+            Ok(book_data) => book_data,
+            Err(e) => {
+                return Ok(Message::ImageFetchFailed {
+                    search_id: image_ref.search_id.clone(),
+                    reason: e.to_string(),
+                    transient: e.is_transient(),
+                })
+            },
+        };
+        let fetch = start.elapsed();
+        let start = Instant::now();
+        let message = decode_image(
+            &picker,
+            image_ref,
+            book_data.details,
+            book_data.raw_bytes,
+            max_pixels,
+            fetch,
+        );
+        latency.record(Kind::Render, "image", start.elapsed());
+
+        // Send the loaded image back to the main loop
+        Ok(message)
+    });
+}
+
+/// Decodes the image fetched in `fetch`, into the message showing it or
+/// telling why it couldn't be.
+fn decode_image(
+    picker: &SharedPicker,
+    image_ref: Arc<BookData>,
+    details: BookData,
+    data: Bytes,
+    max_pixels: Option<u64>,
+    fetch: Duration,
+) -> Message {
+    let image = new_image(
+        picker,
+        Some(image_ref),
+        &details.name,
+        details.search_id.clone(),
+        details.category,
+        data,
+        max_pixels,
+    );
+    match image {
+        Ok(image) => Message::ShowImage(Image {
+            timings: Timings {
+                fetch: Some(fetch),
+                ..image.timings
+            },
+            ..image
+        }),
+        Err(ImageError::Limits(_)) => Message::ImageDecodeFailed {
+            search_id: details.search_id,
+            reason: "image is too large, press ! to load it anyway".to_owned(),
+        },
+        // NOTE: The upstream file itself is broken, this isn't worth crashing over
+        Err(e) => Message::ImageDecodeFailed {
+            search_id: details.search_id,
+            reason: e.to_string(),
+        },
+    }
+}
+
+/// Decodes the image data and builds the protocol state used to render it.
+/// Images wider or taller than `max_pixels`, or taking more memory than that
+/// many RGBA pixels, are refused without being decoded.
+fn new_image(
+    picker: &SharedPicker,
+    book: Option<Arc<BookData>>,
+    name: &str,
+    search_id: String,
+    category: String,
+    data: Bytes,
+    max_pixels: Option<u64>,
+) -> ImageResult<Image> {
+    let mut reader = ImageReader::new(Cursor::new(&data)).with_guessed_format()?;
+    // NOTE: The decoder checks these against the header before allocating anything
+    reader.limits(max_pixels.map_or_else(Limits::no_limits, |max_pixels| {
+        let mut limits = Limits::default();
+        // A side can't be longer than the pixels allowed in all
+        let max_side = Some(u32::try_from(max_pixels).unwrap_or(u32::MAX));
+        limits.max_image_width = max_side;
+        limits.max_image_height = max_side;
+        limits.max_alloc = Some(max_pixels.saturating_mul(DECODED_BYTES_PER_PIXEL));
+        limits
+    }));
+
+    let start = Instant::now();
+    let dyn_image = reader.decode()?;
+    let decode = start.elapsed();
+
+    let height = dyn_image.height() as u16;
+    let width = dyn_image.width() as u16;
+
+    let image_state = picker.new_resize_protocol(dyn_image);
+    let Picker {
+        protocol_type,
+        font_size,
+        ..
+    } = picker.get();
+
+    Ok(Image {
+        book,
+        name: names::normalize(name),
+        search_id,
+        category,
+        state: image_state,
+        data,
+        protocol: protocol_type,
+        font_size,
+        height,
+        width,
+        timings: Timings {
+            decode,
+            ..Default::default()
+        },
+    })
+}
+
+/// Returns the names of the books downloaded to the directory, nothing if it
+/// can't be read.
+async fn downloaded_names(dir: &Path) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let Ok(mut entries) = fs::read_dir(dir).await else {
+        return names;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension().is_some_and(|x| x == "jpeg") {
+            if let Some(name) = path.file_stem() {
+                names.insert(name.to_string_lossy().into_owned());
+            }
+        }
+    }
+    names
+}
+
+fn spawn_download(
+    tasks: &mut JoinSet<Result<Message>>,
+    permits: &Arc<Semaphore>,
+    download: &mut download_queue::Download,
+) {
+    let id = download.id;
+    let path = download.path.clone();
+    let data = download.data.clone();
+    let thumbnail = download.thumbnail;
+    let progress = download.progress.clone();
+    let permits = permits.clone();
+
+    let handle = tasks.spawn(async move {
+        // Impossible for this to explode as the semaphore is never closed
+        let _permit = permits.acquire_owned().await.unwrap();
+        progress.start();
+
+        let data = match thumbnail {
+            // NOTE: Decoding and resizing is CPU bound, so it's kept off the async workers
+            Some(size) => tokio::task::spawn_blocking(move || resize(&data, size))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|x| x.map_err(|e| e.to_string())),
+            None => Ok(data),
+        };
+        let result = match data {
+            Ok(data) => write_download(&path, &data, |percent| progress.set(percent))
+                .await
+                .map_err(|e| e.to_string()),
+            Err(reason) => Err(reason),
+        };
+
+        Ok(Message::DownloadFinished(id, result))
+    });
+    download.handle = Some(handle);
+}
+
+/// Scales the book down so its longest side is `size` pixels, keeping its
+/// aspect ratio, and encodes it as JPEG. Books already that small are only
+/// encoded again.
+fn resize(data: &[u8], size: u32) -> ImageResult<Bytes> {
+    let image = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()?
+        .decode()?
+        .thumbnail(size, size);
+    let mut resized = Cursor::new(Vec::new());
+    // NOTE: JPEG has no alpha channel, so it's dropped first
+    DynamicImage::ImageRgb8(image.into_rgb8()).write_to(&mut resized, ImageFormat::Jpeg)?;
+    Ok(resized.into_inner().into())
+}
+
+async fn write_download(
+    path: &Path,
+    data: &Bytes,
+    on_progress: impl Fn(u8),
+) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let partial = download_queue::partial_path(path);
+    let mut file = File::create(&partial).await?;
+
+    // Write in chunks so the progress can be reported
+    let chunk_size = (data.len() / 100).max(64 * 1024);
+    let mut written = 0;
+    for chunk in data.chunks(chunk_size) {
+        file.write_all(chunk).await?;
+        written += chunk.len();
+        on_progress((written * 100 / data.len()) as u8);
+    }
+    file.flush().await?;
+    // Only a complete download takes the book's name
+    fs::rename(&partial, path).await
+}
+
+async fn handle_event(app: &App, events: &mut EventStream) -> Result<Option<Message>> {
+    let Ok(event) = tokio::time::timeout(poll_timeout(app), events.next()).await else {
+        return Ok(None);
+    };
+    match event.transpose()? {
+        Some(Event::Key(key)) if key.kind == event::KeyEventKind::Press => Ok(handle_key(app, key)),
+        Some(Event::FocusGained) => Ok(Some(Message::FocusChanged(true))),
+        Some(Event::FocusLost) => Ok(Some(Message::FocusChanged(false))),
+        _ => Ok(None),
+    }
+}
+
+/// Wakes up every tick while something needs to be redrawn or picked up soon,
+/// otherwise waits for input for much longer to keep an idle app quiet.
+fn poll_timeout(app: &App) -> Duration {
+    // Keep redrawing until the reordered favorite's highlight fades
+    let highlighting = app
+        .favorite_moved_at
+        .is_some_and(|x| x.elapsed() < MOVED_FAVORITE_HIGHLIGHT);
+    // Wake up in time to close the popup
+    let popup_timing = app.config.popup_timeout_secs.is_some()
+        && (app.download_popup_opened_at.is_some() || app.api_warning_opened_at.is_some());
+    if app.paused {
+        return IDLE_POLL_TIMEOUT;
+    }
+    if app.transition.is_some() {
+        return transition::FRAME_INTERVAL;
+    }
+    if app.tasks.is_empty()
+        && app.toast.is_none()
+        && app.suggestions_deadline.is_none()
+        && app.hover_timer.is_none()
+        && app.pending_image.is_none()
+        && !highlighting
+        && !popup_timing
+    {
+        IDLE_POLL_TIMEOUT
+    } else {
+        Duration::from_millis(app.config.tick_ms)
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+fn handle_key(app: &App, key: event::KeyEvent) -> Option<Message> {
+    // NOTE: Raw mode turns Ctrl+C into a key press instead of a SIGINT, so quit the
+    // same way `q` does to restore the terminal on the way out
+    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        return Some(Message::Exit);
+    }
+    if app.screensaver.is_some() {
+        return Some(Message::StopScreensaver);
+    }
+    if key.code == KeyCode::F(12) {
+        return Some(Message::CopyState);
+    }
+    if let Some(popup) = app.api_warning_popup() {
+        return popup.handle_key(key);
+    }
+
+    match app.running_state {
+        RunningState::Comparing => match key.code {
+            KeyCode::Char('h') | KeyCode::Left => Some(Message::PanImage(-1, 0)),
+            KeyCode::Char('j') | KeyCode::Down => Some(Message::PanImage(0, 1)),
+            KeyCode::Char('k') | KeyCode::Up => Some(Message::PanImage(0, -1)),
+            KeyCode::Char('l') | KeyCode::Right => Some(Message::PanImage(1, 0)),
+            KeyCode::Char('+' | '=') => Some(Message::ZoomImage(1)),
+            KeyCode::Char('-') => Some(Message::ZoomImage(-1)),
+            KeyCode::Char('s') => Some(Message::SwapCompared),
+            KeyCode::Esc | KeyCode::Char('q' | 'C') => Some(Message::CloseCompare),
+            _ => None,
+        },
+        RunningState::EditingNote => match key.code {
+            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Message::SaveNote)
+            },
+            KeyCode::Esc => Some(Message::CancelNote),
+            _ => Some(Message::HandleNoteInput(key)),
+        },
+        RunningState::FindingInList => match key.code {
+            KeyCode::Enter => Some(Message::AcceptFind),
+            KeyCode::Esc => Some(Message::CancelFind),
+            KeyCode::Down | KeyCode::Tab => Some(Message::FindNext),
+            KeyCode::Up | KeyCode::BackTab => Some(Message::FindPrevious),
+            _ => Some(Message::HandleFindInput(key)),
+        },
+        RunningState::Searching => match key.code {
+            KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Message::ToggleSearchScope)
+            },
+            KeyCode::Enter if app.suggestions.state.selected().is_some() => {
+                Some(Message::CompleteSuggestion)
+            },
+            KeyCode::Tab | KeyCode::Down if !app.suggestions.is_empty() => {
+                Some(Message::MoveDownSuggestions)
+            },
+            KeyCode::BackTab | KeyCode::Up if app.suggestions.state.selected().is_some() => {
+                Some(Message::MoveUpSuggestions)
+            },
+            KeyCode::Enter
+                if !app.search_input.value().is_empty() || app.config.empty_search_browses_all =>
+            {
+                // Only allow the user to press enter if they've entered some search query,
+                // unless an empty one lists every image
+                Some(Message::ShowSearchResults)
+            },
+            _ => Some(Message::HandleSearchInput(key)),
+        },
+        _ if key.code == KeyCode::Char('q') && key.modifiers.contains(KeyModifiers::CONTROL) => {
+            match app.running_state {
+                RunningState::BrowsingCategories
+                | RunningState::BrowsingImages
+                | RunningState::ShowingDownloadQueue => Some(Message::ToggleDownloadQueue),
+                _ => None,
+            }
+        },
+        _ if key.code == KeyCode::Char('r') && key.modifiers.contains(KeyModifiers::CONTROL) => {
+            match app.running_state {
+                RunningState::BrowsingCategories
+                | RunningState::BrowsingImages
+                | RunningState::BrowsingByTag
+                | RunningState::EmptyResults => Some(Message::Reset),
+                _ => None,
+            }
+        },
+        _ if key.code == KeyCode::Char('z') && key.modifiers.contains(KeyModifiers::CONTROL) => {
+            match app.running_state {
+                RunningState::BrowsingCategories | RunningState::BrowsingImages => {
+                    Some(Message::Undo)
+                },
+                _ => None,
+            }
+        },
+        _ if key.code == KeyCode::Char('f') && key.modifiers.contains(KeyModifiers::CONTROL) => {
+            match app.running_state {
+                RunningState::BrowsingCategories | RunningState::BrowsingImages => {
+                    Some(Message::OpenFind)
+                },
+                _ => None,
+            }
+        },
+        _ if key.code == KeyCode::Char('b') && key.modifiers.contains(KeyModifiers::CONTROL) => {
+            match app.running_state {
+                RunningState::BrowsingCategories | RunningState::BrowsingImages => {
+                    Some(Message::TogglePin)
+                },
+                _ => None,
+            }
+        },
+        RunningState::BrowsingCategories | RunningState::BrowsingImages
+            if key.code == KeyCode::Tab && (app.image.is_some() || app.pinned.is_some()) =>
+        {
+            Some(Message::SwitchFocus)
+        },
+        RunningState::BrowsingCategories | RunningState::BrowsingImages
+            if key.code == KeyCode::Char('0') =>
+        {
+            Some(Message::ResetView)
+        },
+        _ if key.code == KeyCode::Char('p') && key.modifiers.contains(KeyModifiers::CONTROL) => {
+            match app.running_state {
+                RunningState::BrowsingCategories | RunningState::BrowsingImages => {
+                    Some(Message::OpenCategoryPalette)
+                },
+                _ => None,
+            }
+        },
+        // NOTE: Letters bound above keep their own meaning
+        RunningState::BrowsingCategories if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            match key.code {
+                KeyCode::Char(c) if c.is_ascii_alphabetic() => Some(Message::JumpToLetter(c)),
+                _ => None,
+            }
+        },
+        // Nothing below is bound with Ctrl or Alt, don't mistake those for the plain key
+        RunningState::BrowsingCategories
+        | RunningState::BrowsingImages
+        | RunningState::BrowsingByTag
+        | RunningState::ShowingDownloadQueue
+        | RunningState::ShowingHelp
+        | RunningState::ShowingFavorites
+        | RunningState::ShowingBlacklist
+            if key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+        {
+            None
+        },
+        RunningState::BrowsingCategories => match key.code {
+            // There's no image list to step through
+            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right
+                if app.focus != Focus::List =>
+            {
+                None
+            },
+            KeyCode::Up => Some(Message::MoveUpCategories),
+            KeyCode::Down => Some(Message::MoveDownCategories),
+            KeyCode::Right | KeyCode::Enter => app
+                .categories
+                .state
+                .selected()
+                .and_then(|selected| app.categories.get(selected))
+                .map(|x| Message::LoadList(ListSource::Category(x.to_owned()))),
+            KeyCode::Char('T') => Some(Message::LoadTags),
+            KeyCode::Char('>') => Some(Message::ChangePrefetchedCategories(1)),
+            KeyCode::Char('<') => Some(Message::ChangePrefetchedCategories(-1)),
+            KeyCode::Char('q') => Some(Message::Exit),
+            KeyCode::Char('s' | '/') => Some(Message::Search),
+            KeyCode::Char('d') if app.focused_image().is_some() => Some(Message::DownloadImage),
+            KeyCode::Char('D') if app.focused_image().is_some() => {
+                Some(Message::DownloadThumbnails)
+            },
+            KeyCode::Char('y') if app.focused_image().is_some() => Some(Message::CopyDataUri),
+            KeyCode::Char('Y') if app.focused_image().is_some() => Some(Message::CopyToTempFile),
+            KeyCode::Char('t') if app.image.is_some() => Some(Message::EditNote),
+            KeyCode::Char('1') if app.image.is_some() => Some(Message::ToggleNativeSize),
+            KeyCode::Char('h') if app.native_image.is_some() => Some(Message::PanImage(-1, 0)),
+            KeyCode::Char('j') if app.native_image.is_some() => Some(Message::PanImage(0, 1)),
+            KeyCode::Char('k') if app.native_image.is_some() => Some(Message::PanImage(0, -1)),
+            KeyCode::Char('l') if app.native_image.is_some() => Some(Message::PanImage(1, 0)),
+            KeyCode::Char('E') => Some(Message::ExportBookmarks),
+            KeyCode::Char('F') => Some(Message::ToggleFavorites),
+            KeyCode::Char('B') => Some(Message::ToggleBlacklist),
+            KeyCode::Char('?') => Some(Message::ToggleHelp),
+            _ => None,
+        },
+        RunningState::BrowsingImages => match key.code {
+            KeyCode::Enter if app.confirming_image.is_some() => Some(Message::AcceptImageLoad),
+            KeyCode::Esc if app.confirming_image.is_some() => Some(Message::SkipImageLoad),
+            KeyCode::Up | KeyCode::Left if app.focus != Focus::List => {
+                Some(Message::ShowPreviousImage)
+            },
+            KeyCode::Down | KeyCode::Right if app.focus != Focus::List => {
+                Some(Message::ShowNextImage)
+            },
+            KeyCode::Char('q') => Some(Message::Exit),
+            KeyCode::Char('s' | '/') => Some(Message::Search),
+            KeyCode::Char('d') if app.focused_image().is_some() => Some(Message::DownloadImage),
+            KeyCode::Char('D') if app.focused_image().is_some() => {
+                Some(Message::DownloadThumbnails)
+            },
+            KeyCode::Char('c') => Some(Message::ClearSeen),
+            KeyCode::Char('m') if app.selected_image().is_some() => Some(Message::ToggleMark),
+            KeyCode::Char('C') => Some(Message::Compare),
+            KeyCode::Char('y') if app.focused_image().is_some() => Some(Message::CopyDataUri),
+            KeyCode::Char('Y') if app.focused_image().is_some() => Some(Message::CopyToTempFile),
+            KeyCode::Char('t') if app.image.is_some() => Some(Message::EditNote),
+            KeyCode::Char('1') if app.image.is_some() => Some(Message::ToggleNativeSize),
+            KeyCode::Char('h') if app.native_image.is_some() => Some(Message::PanImage(-1, 0)),
+            KeyCode::Char('j') if app.native_image.is_some() => Some(Message::PanImage(0, 1)),
+            KeyCode::Char('k') if app.native_image.is_some() => Some(Message::PanImage(0, -1)),
+            KeyCode::Char('l') if app.native_image.is_some() => Some(Message::PanImage(1, 0)),
+            KeyCode::Char('f') => Some(Message::ToggleFavorite),
+            KeyCode::Char('E') => Some(Message::ExportBookmarks),
+            KeyCode::Char('F') => Some(Message::ToggleFavorites),
+            KeyCode::Char('H') if app.selected_image().is_some() => Some(Message::HideImage),
+            KeyCode::Char('B') => Some(Message::ToggleBlacklist),
+            KeyCode::Char('?') => Some(Message::ToggleHelp),
+            KeyCode::Char('X') if app.list_source.is_some() => Some(Message::ExportList),
+            KeyCode::Char('R') if app.list_source.is_some() => Some(Message::ForceReloadImageList),
+            KeyCode::F(5) if app.image.as_ref().is_some_and(|x| x.book.is_some()) => {
+                Some(Message::ReloadImage)
+            },
+            KeyCode::Up => Some(Message::MoveUpImages),
+            KeyCode::Down => Some(Message::MoveDownImages),
+            KeyCode::Left if matches!(app.list_source, Some(ListSource::Tag(..))) => {
+                Some(Message::LoadTags)
+            },
+            KeyCode::Left => Some(Message::BrowseCategories),
+            KeyCode::Char('x') if app.collapsed > 0 => Some(Message::ExpandResults),
+            KeyCode::Char('L') => Some(Message::SwitchList),
+            KeyCode::Char('o') => Some(Message::CycleSort),
+            KeyCode::Char(']') => Some(Message::NextNotDownloaded),
+            KeyCode::Char('[') => Some(Message::PreviousNotDownloaded),
+            KeyCode::Char('n') => Some(Message::TogglePreview),
+            KeyCode::Right | KeyCode::Enter
+                if app.collapsed > 0
+                    && app.images_list.state.selected() == Some(app.visible_images()) =>
+            {
+                Some(Message::ExpandResults)
+            },
+            // NOTE: Most terminals send Shift+Enter as a plain Enter, so `!` does the same
+            KeyCode::Char('!') if app.selected_image().is_some() => Some(Message::ForceLoadImage),
+            KeyCode::Enter
+                if key.modifiers.contains(KeyModifiers::SHIFT)
+                    && app.selected_image().is_some() =>
+            {
+                Some(Message::ForceLoadImage)
+            },
+            KeyCode::Right | KeyCode::Enter => {
+                if app.selected_image().is_some() {
+                    Some(Message::LoadImage)
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        },
+        RunningState::BrowsingByTag => match key.code {
+            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right
+                if app.focus != Focus::List =>
+            {
+                None
+            },
+            KeyCode::Up => Some(Message::MoveUpTags),
+            KeyCode::Down => Some(Message::MoveDownTags),
+            KeyCode::Left | KeyCode::Esc => Some(Message::BrowseCategories),
+            KeyCode::Right | KeyCode::Enter => app.tags_list.state.selected().map(|selected| {
+                let tag = app.tags[selected].clone();
+                let categories = tags::categories_with(&tag, app.categories.iter());
+                Message::LoadList(ListSource::Tag(tag, categories))
+            }),
+            KeyCode::Char('q') => Some(Message::Exit),
+            KeyCode::Char('?') => Some(Message::ToggleHelp),
+            _ => None,
+        },
+        RunningState::EmptyResults => match key.code {
+            KeyCode::Backspace => Some(Message::GoBack),
+            KeyCode::Char('L') => Some(Message::SwitchList),
+            KeyCode::Left | KeyCode::Esc => Some(Message::BrowseCategories),
+            KeyCode::Char('q') => Some(Message::Exit),
+            _ => None,
+        },
+        RunningState::ShowingDownloadPopup => app.download_popup().handle_key(key),
+        RunningState::ShowingDownloadQueue => match key.code {
+            KeyCode::Up => Some(Message::MoveUpDownloads),
+            KeyCode::Down => Some(Message::MoveDownDownloads),
+            KeyCode::Char('r') => Some(Message::RetryDownload),
+            KeyCode::Char('x') | KeyCode::Delete => Some(Message::CancelDownload),
+            KeyCode::Esc => Some(Message::ToggleDownloadQueue),
+            _ => None,
+        },
+        RunningState::ShowingFavorites => match key.code {
+            KeyCode::Up if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                Some(Message::MoveFavoriteUp)
+            },
+            KeyCode::Down if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                Some(Message::MoveFavoriteDown)
+            },
+            KeyCode::Up => Some(Message::MoveUpFavorites),
+            KeyCode::Down => Some(Message::MoveDownFavorites),
+            KeyCode::Char('F') | KeyCode::Esc => Some(Message::ToggleFavorites),
+            _ => None,
+        },
+        RunningState::ShowingBlacklist => match key.code {
+            KeyCode::Up => Some(Message::MoveUpBlacklist),
+            KeyCode::Down => Some(Message::MoveDownBlacklist),
+            KeyCode::Char('u') | KeyCode::Enter | KeyCode::Delete => Some(Message::UnhideImage),
+            KeyCode::Char('B') | KeyCode::Esc => Some(Message::ToggleBlacklist),
+            _ => None,
+        },
+        RunningState::ShowingHelp => match key.code {
+            KeyCode::Up => Some(Message::MoveUpHelp),
+            KeyCode::Down => Some(Message::MoveDownHelp),
+            KeyCode::Char('?' | 'q') | KeyCode::Esc => Some(Message::ToggleHelp),
+            _ => None,
+        },
+        RunningState::ShowingCategoryPalette => match key.code {
+            KeyCode::Up => Some(Message::MoveUpCategoryPalette),
+            KeyCode::Down => Some(Message::MoveDownCategoryPalette),
+            KeyCode::Enter => app
+                .category_palette
+                .state
+                .selected()
+                .and_then(|i| app.category_palette.get(i))
+                .map(|x| Message::LoadList(ListSource::Category(x.to_owned()))),
+            KeyCode::Esc => Some(Message::CloseCategoryPalette),
+            _ => Some(Message::HandleCategoryPaletteInput(key)),
+        },
+        RunningState::Exit | RunningState::Loading => None,
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    errors::install_hooks()?;
+    let (mut config, config_errors) = Config::load()?;
+    if cli.font_size.is_some() {
+        config.font_size = cli.font_size;
+    }
+    if cli.export_config {
+        config.user_agent = Some(http::user_agent(&config));
+        config.headers = http::headers(&config);
+        print!("{}", config.to_toml_string());
+        return Ok(());
+    }
+    http::init(&config)?;
+    if cli.benchmark {
+        return benchmark::run(cli.output).await;
+    }
+    let mut term = tui::init()?;
+    let mut shutdown = signals::shutdown()
+        .wrap_err("unable to listen for signals")?
+        .fuse();
+    let (picker, font_size_guessed) =
+        tui::init_image_picker(cli.redetect, config.font_size.map(|[w, h]| (w, h)));
+    if let Some(protocol) = config.protocol {
+        picker.set_protocol_type(protocol.into());
+    }
+    let mut app = App {
+        config,
+        seen: Seen::load()?,
+        favorites: Favorites::load()?,
+        blacklist: Blacklist::load()?,
+        notes: Notes::load()?,
+        known_books: KnownBooks::load()?,
+        session: Session::load()?,
+        placeholder: Some(
+            new_image(
+                &picker,
+                None,
+                "placeholder",
+                String::new(),
+                String::new(),
+                Bytes::from_static(PLACEHOLDER_IMAGE),
+                None,
+            )
+            .wrap_err("unable to decode the placeholder image")?,
+        ),
+        picker,
+        deep_link_target: cli.open,
+        prefetched_categories: PREFETCHED_CATEGORIES,
+        latency: if cli.verbose {
+            Latency::with_log()?
+        } else {
+            Latency::default()
+        },
+        ..Default::default()
+    };
+    app.toast = config_errors_toast(&config_errors);
+    if font_size_guessed && app.toast.is_none() {
+        app.toast = Some(Toast::new(
+            "Font size unknown, images may be misscaled: set `font_size` in the config",
+        ));
+    }
+    if cli.file.is_none() {
+        // NOTE: Checked in the background, a slow API shouldn't hold up the first frame
+        app.tasks.spawn(async {
+            Ok(Message::ApiChecked(
+                api::check_api_compatibility().await.ok(),
+            ))
+        });
+    }
+    let mut first_launch = true;
+    if let Some(path) = cli.file {
+        // Skip the API entirely and go straight to showing the image
+        app.image = Some(open_file(&app.picker, &path)?);
+        app.running_state = RunningState::BrowsingImages;
+        first_launch = false;
+    }
+
+    run(&mut app, &mut term, &mut shutdown, first_launch).await?;
+
+    // Don't leave downloads and loads running in the background while exiting
+    app.tasks.abort_all();
+    for download in &app.downloads.items {
+        download.cancel();
+    }
+    save_session(&app)?;
+
+    tui::restore()?;
+    // NOTE: Printed after leaving the alternate screen, so it stays in the
+    // scrollback and can be piped
+    if app.config.exit_summary || cli.exit_summary_json {
+        if let Some(summary) = app.downloads.exit_summary(cli.exit_summary_json) {
+            println!("{summary}");
+        }
+    }
+    Ok(())
+}
+
+/// Draws the app and handles its messages until it's exited. Loads the
+/// categories first on the `first_launch`.
+async fn run(
+    app: &mut App,
+    term: &mut tui::Tui,
+    shutdown: &mut Fuse<oneshot::Receiver<()>>,
+    mut first_launch: bool,
+) -> Result<()> {
+    // Messages waiting to be handled, in order
+    let mut messages = VecDeque::new();
+    let mut events = EventStream::new();
+    app.last_input = Some(Instant::now());
+
+    while app.running_state != RunningState::Exit {
+        term.draw(|f| view(app, f))?;
+        app.record_sent();
+
+        // Don't wait for input before loading the categories or while messages are
+        // still queued up
+        let mut message = None;
+        // Quit like `q` does, so the terminal is restored
+        let mut shutting_down = false;
+        if !first_launch && messages.is_empty() {
+            // NOTE: A signal wakes the app up right away, rather than at the next
+            // input or tick
+            tokio::select! {
+                event = handle_event(app, &mut events) => message = event?,
+                Ok(()) = &mut *shutdown => shutting_down = true,
+            }
+        }
+        if shutting_down || matches!((&mut *shutdown).now_or_never(), Some(Ok(()))) {
+            app.running_state = RunningState::Exit;
+            break;
+        }
+
+        if message.is_some() {
+            app.last_input = Some(Instant::now());
+        } else {
+            message = app
+                .preload_due()
+                .or_else(|| app.screensaver_due())
+                .or_else(|| app.popup_dismiss_due());
+        }
+
+        if message.is_none()
+            && app
+                .suggestions_deadline
+                .is_some_and(|x| x <= Instant::now())
+        {
+            message = Some(Message::FetchSuggestions);
+        }
+        if message.is_none()
+            && app
+                .auto_refresh_deadline
+                .is_some_and(|x| x <= Instant::now())
+        {
+            message = Some(Message::AutoRefreshList);
+        }
+
+        if first_launch {
+            message = Some(Message::LoadCategories);
+            first_launch = false;
+        }
+
+        messages.extend(message);
+
+        while let Some(msg) = app.tasks.try_join_next() {
+            match msg {
+                Ok(msg) => messages.push_back(msg.unwrap()),
+                // Cancelled downloads are expected to be aborted
+                Err(e) if e.is_cancelled() => {},
+                Err(e) => panic::resume_unwind(e.into_panic()),
+            }
+        }
+        // NOTE: Picked up even while a key is held, that's when images pile up
+        messages.extend(app.pending_image_due());
+
+        for _ in 0..MAX_MESSAGES_PER_FRAME {
+            let Some(msg) = messages.pop_front() else {
+                break;
+            };
+            let from_state = app.running_state;
+            messages.extend(app.update(msg).await);
+            if let Some(transition) = Transition::between(from_state, app.running_state) {
+                app.transition = Some(transition);
+            }
+        }
+
+        if app.toast.as_ref().is_some_and(Toast::is_expired) {
+            app.toast = None;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the book names are the same, ignoring how they're written.
+fn same_name(a: &str, b: &str) -> bool {
+    names::normalize(a).to_lowercase() == names::normalize(b).to_lowercase()
+}
+
+/// Tells the user about the first invalid option, and how many more there are.
+fn config_errors_toast(errors: &[ConfigError]) -> Option<Toast> {
+    let error = errors.first()?;
+    let more = match errors.len() {
+        1 => String::new(),
+        n => format!(" (and {} more)", n - 1),
+    };
+    Some(Toast::new(format!(
+        "Invalid config, using the default: {error}{more}"
+    )))
+}
+
+/// Reads and decodes a local image file.
+fn open_file(picker: &SharedPicker, path: &Path) -> Result<Image> {
+    let data = std::fs::read(path)
+        .wrap_err_with(|| format!("unable to read the image file: `{}`", path.display()))?;
+    let name = path
+        .file_stem()
+        .map_or_else(String::new, |x| x.to_string_lossy().into_owned());
+    new_image(
+        picker,
+        None,
+        &name,
+        path.display().to_string(),
+        String::new(),
+        data.into(),
+        None,
+    )
+    .wrap_err_with(|| format!("unable to decode the image file: `{}`", path.display()))
+}
+
+fn save_session(app: &App) -> Result<()> {
+    // Nothing was browsed, keep the previous session around
+    if app.categories.is_empty() {
+        return Ok(());
+    }
+
+    let category = match &app.list_source {
+        Some(ListSource::Category(category)) => Some(category.clone()),
+        _ => None,
+    };
+    Session {
+        categories: Some(app.categories.save_state()),
+        images: category.as_ref().map(|_| app.images_list.save_state()),
+        category,
+        search_all: app.session.search_all,
+    }
+    .save()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::sync::OnceLock};
+
+    /// Points the data, config and cache directories at a temporary one, so
+    /// tests don't touch the user's.
+    fn isolate_dirs() {
+        static DIR: OnceLock<tempfile::TempDir> = OnceLock::new();
+        DIR.get_or_init(|| {
+            let dir = tempfile::tempdir().unwrap();
+            for var in ["XDG_DATA_HOME", "XDG_CONFIG_HOME", "XDG_CACHE_HOME"] {
+                std::env::set_var(var, dir.path());
+            }
+            let downloads = dir.path().join("Downloads");
+            std::fs::write(
+                dir.path().join("user-dirs.dirs"),
+                format!("XDG_DOWNLOAD_DIR=\"{}\"\n", downloads.display()),
+            )
+            .unwrap();
+            dir
+        });
+    }
+
+    fn app() -> App {
+        isolate_dirs();
+        App {
+            config: Config {
+                auto_load_first_image: false,
+                ..Config::default()
+            },
+            ..App::default()
+        }
+    }
+
+    fn book(name: &str, category: &str) -> Arc<BookData> { Arc::new(book_data(name, category)) }
+
+    fn book_data(name: &str, category: &str) -> BookData {
+        BookData::from_json(HashMap::from(
+            [
+                ("name", name),
+                ("category", category),
+                ("date_added", "2024-01-01 00:00:00+0000"),
+                ("search_id", &format!("{category}/{name}")),
+                ("commit_url", ""),
+                ("commit_author", ""),
+            ]
+            .map(|(key, value)| (key.to_owned(), value.to_owned())),
+        ))
+    }
+
+    fn sources() -> [ListSource; 4] {
+        [
+            ListSource::Category("Rust".to_owned()),
+            ListSource::Search("rust".to_owned(), None),
+            ListSource::Search(String::new(), Some("Rust".to_owned())),
+            ListSource::Tag("Rust".to_owned(), vec!["Rust".to_owned()]),
+        ]
+    }
+
+    #[tokio::test]
+    async fn list_loads_for_every_source() {
+        for source in sources() {
+            let mut app = app();
+            app.running_state = RunningState::BrowsingCategories;
+
+            app.update(Message::LoadList(source.clone())).await;
+            assert_eq!(
+                app.running_state,
+                RunningState::BrowsingImages,
+                "{source:?}"
+            );
+            assert_eq!(app.loading_list.as_ref(), Some(&source));
+
+            let images = vec![book("Rust Book", "Rust"), book("Rustonomicon", "Rust")];
+            app.update(Message::ListLoaded(source.clone(), images))
+                .await;
+            assert_eq!(
+                app.running_state,
+                RunningState::BrowsingImages,
+                "{source:?}"
+            );
+            assert_eq!(app.loading_list, None);
+            assert_eq!(app.list_source.as_ref(), Some(&source));
+            assert_eq!(app.images_list.len(), 2, "{source:?}");
+            assert_eq!(app.images_list.state.selected(), Some(0));
+            app.tasks.abort_all();
+        }
+    }
+
+    #[tokio::test]
+    async fn empty_list_shows_empty_results_for_every_source() {
+        for source in sources() {
+            let mut app = app();
+            app.update(Message::LoadList(source.clone())).await;
+            app.update(Message::ListLoaded(source.clone(), Vec::new()))
+                .await;
+            assert_eq!(app.running_state, RunningState::EmptyResults, "{source:?}");
+            assert_eq!(app.list_source.as_ref(), Some(&source));
+            app.tasks.abort_all();
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_list_load_keeps_the_shown_list_for_every_source() {
+        for source in sources() {
+            let mut app = app();
+            let shown = ListSource::Category("Go".to_owned());
+            app.update(Message::LoadList(shown.clone())).await;
+            app.update(Message::ListLoaded(
+                shown.clone(),
+                vec![book("Go Book", "Go")],
+            ))
+            .await;
+
+            app.update(Message::LoadList(source.clone())).await;
+            app.update(Message::ListLoadFailed(
+                source.clone(),
+                "offline".to_owned(),
+            ))
+            .await;
+            assert_eq!(app.loading_list, None, "{source:?}");
+            assert_eq!(app.list_source.as_ref(), Some(&shown));
+            assert_eq!(app.running_state, RunningState::BrowsingImages);
+            assert!(app.toast.is_some_and(|x| x.message.contains("offline")));
+            app.tasks.abort_all();
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_first_list_load_goes_back_to_the_categories() {
+        for source in sources() {
+            let mut app = app();
+            app.running_state = RunningState::BrowsingCategories;
+            app.update(Message::LoadList(source.clone())).await;
+            app.update(Message::ListLoadFailed(
+                source.clone(),
+                "offline".to_owned(),
+            ))
+            .await;
+            assert_eq!(
+                app.running_state,
+                RunningState::BrowsingCategories,
+                "{source:?}"
+            );
+            assert_eq!(app.loading_list, None);
+            app.tasks.abort_all();
+        }
+    }
+
+    #[tokio::test]
+    async fn stale_list_load_failure_is_ignored() {
+        let mut app = app();
+        let [category, search, ..] = sources();
+        app.update(Message::LoadList(category.clone())).await;
+        app.update(Message::LoadList(search.clone())).await;
+        app.update(Message::ListLoadFailed(category, "offline".to_owned()))
+            .await;
+        assert_eq!(app.loading_list, Some(search));
+        assert!(app.toast.is_none());
+        app.tasks.abort_all();
+    }
+
+    #[test]
+    fn truncated_image_fails_to_decode() {
+        let mut jpeg = Vec::new();
+        DynamicImage::new_rgb8(64, 64)
+            .write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+            .unwrap();
+        jpeg.truncate(jpeg.len() / 2);
+
+        let message = decode_image(
+            &SharedPicker::default(),
+            book("Rust Book", "Rust"),
+            book_data("Rust Book", "Rust"),
+            Bytes::from(jpeg),
+            None,
+            Duration::ZERO,
+        );
+        assert!(matches!(
+            message,
+            Message::ImageDecodeFailed { search_id, .. } if search_id == "Rust/Rust Book"
+        ));
+    }
+
+    #[test]
+    fn huge_image_is_refused_before_decoding() {
+        // A PNG header claiming 100000x100000 pixels, followed by no pixel data
+        const PNG: &[u8] = &[
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x01, 0x86, 0xa0, 0x00, 0x01, 0x86, 0xa0, 0x08, 0x02, 0x00, 0x00,
+            0x00, 0x27, 0x30, 0x9c, 0x9f, 0x00, 0x00, 0x00, 0x00, 0x49, 0x44, 0x41, 0x54, 0x35,
+            0xaf, 0x06, 0x1e,
+        ];
+
+        let message = decode_image(
+            &SharedPicker::default(),
+            book("Rust Book", "Rust"),
+            book_data("Rust Book", "Rust"),
+            Bytes::from_static(PNG),
+            Some(50_000_000),
+            Duration::ZERO,
+        );
+        assert!(matches!(
+            message,
+            Message::ImageDecodeFailed { reason, .. } if reason.contains("too large")
+        ));
+    }
+
+    #[test]
+    fn deep_image_is_refused_by_its_decoded_size() {
+        // A PNG header claiming 3000x3000 16-bit RGBA: under the pixel cap, but 72 MB
+        // decoded
+        const PNG: &[u8] = &[
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x0b, 0xb8, 0x00, 0x00, 0x0b, 0xb8, 0x10, 0x06, 0x00, 0x00,
+            0x00, 0x21, 0x85, 0x29, 0x3a, 0x00, 0x00, 0x00, 0x00, 0x49, 0x44, 0x41, 0x54, 0x35,
+            0xaf, 0x06, 0x1e,
+        ];
+
+        let message = decode_image(
+            &SharedPicker::default(),
+            book("Rust Book", "Rust"),
+            book_data("Rust Book", "Rust"),
+            Bytes::from_static(PNG),
+            Some(10_000_000),
+            Duration::ZERO,
+        );
+        assert!(matches!(
+            message,
+            Message::ImageDecodeFailed { reason, .. } if reason.contains("too large")
+        ));
+    }
+
+    #[tokio::test]
+    async fn bang_force_loads_the_image() {
+        let mut app = app();
+        app.running_state = RunningState::BrowsingImages;
+        app.install_list(
+            ListSource::Category("Rust".to_owned()),
+            vec![book("Rust Book", "Rust")],
+            false,
+        );
+
+        let key = KeyEvent::new(KeyCode::Char('!'), KeyModifiers::NONE);
+        assert!(matches!(
+            handle_key(&app, key),
+            Some(Message::ForceLoadImage)
+        ));
+        app.tasks.abort_all();
+    }
+
+    #[tokio::test]
+    async fn prefetched_categories_stay_in_range() {
+        let mut app = app();
+        app.running_state = RunningState::BrowsingCategories;
+        app.prefetched_categories = PREFETCHED_CATEGORIES;
+
+        let key = KeyEvent::new(KeyCode::Char('>'), KeyModifiers::NONE);
+        for _ in 0..=MAX_PREFETCHED_CATEGORIES {
+            let message = handle_key(&app, key).unwrap();
+            app.update(message).await;
+        }
+        assert_eq!(app.prefetched_categories, MAX_PREFETCHED_CATEGORIES);
+
+        let key = KeyEvent::new(KeyCode::Char('<'), KeyModifiers::NONE);
+        for _ in 0..=MAX_PREFETCHED_CATEGORIES {
+            let message = handle_key(&app, key).unwrap();
+            app.update(message).await;
+        }
+        assert_eq!(app.prefetched_categories, 0);
+        app.tasks.abort_all();
+    }
+
+    fn press(app: &App, code: KeyCode) -> Option<Message> {
+        handle_key(app, KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    const ARROWS: [KeyCode; 4] = [KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right];
+
+    #[test]
+    fn arrows_in_categories_follow_the_focus() {
+        let mut app = app();
+        app.running_state = RunningState::BrowsingCategories;
+        app.categories = StatefulList::with_items(vec!["Rust".to_owned(), "C++".to_owned()]);
+
+        app.focus = Focus::List;
+        assert!(matches!(
+            press(&app, KeyCode::Up),
+            Some(Message::MoveUpCategories)
+        ));
+        assert!(matches!(
+            press(&app, KeyCode::Down),
+            Some(Message::MoveDownCategories)
+        ));
+        assert!(press(&app, KeyCode::Left).is_none());
+        assert!(matches!(
+            press(&app, KeyCode::Right),
+            Some(Message::LoadList(ListSource::Category(x))) if x == "Rust"
+        ));
+
+        // There's no image list to step through from the image pane
+        app.focus = Focus::Image;
+        for code in ARROWS {
+            assert!(press(&app, code).is_none(), "{code:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn arrows_in_images_follow_the_focus() {
+        let mut app = app();
+        app.running_state = RunningState::BrowsingImages;
+        app.install_list(
+            ListSource::Category("Rust".to_owned()),
+            vec![book("Rust Book", "Rust"), book("Rustonomicon", "Rust")],
+            false,
+        );
+
+        app.focus = Focus::List;
+        assert!(matches!(
+            press(&app, KeyCode::Up),
+            Some(Message::MoveUpImages)
+        ));
+        assert!(matches!(
+            press(&app, KeyCode::Down),
+            Some(Message::MoveDownImages)
+        ));
+        assert!(matches!(
+            press(&app, KeyCode::Left),
+            Some(Message::BrowseCategories)
+        ));
+        assert!(matches!(
+            press(&app, KeyCode::Right),
+            Some(Message::LoadImage)
+        ));
+
+        app.focus = Focus::Image;
+        for code in [KeyCode::Up, KeyCode::Left] {
+            assert!(
+                matches!(press(&app, code), Some(Message::ShowPreviousImage)),
+                "{code:?}"
+            );
+        }
+        for code in [KeyCode::Down, KeyCode::Right] {
+            assert!(
+                matches!(press(&app, code), Some(Message::ShowNextImage)),
+                "{code:?}"
+            );
+        }
+        app.tasks.abort_all();
+    }
+
+    #[test]
+    fn arrows_in_tags_follow_the_focus() {
+        let mut app = app();
+        app.running_state = RunningState::BrowsingByTag;
+        app.categories = StatefulList::with_items(vec!["Rust".to_owned()]);
+        app.tags = vec!["Rust".to_owned()];
+        app.tags_list = StatefulList::with_items(vec!["Rust (1)".to_owned()]);
+
+        app.focus = Focus::List;
+        assert!(matches!(
+            press(&app, KeyCode::Up),
+            Some(Message::MoveUpTags)
+        ));
+        assert!(matches!(
+            press(&app, KeyCode::Down),
+            Some(Message::MoveDownTags)
+        ));
+        assert!(matches!(
+            press(&app, KeyCode::Left),
+            Some(Message::BrowseCategories)
+        ));
+        assert!(matches!(
+            press(&app, KeyCode::Right),
+            Some(Message::LoadList(ListSource::Tag(tag, categories)))
+                if tag == "Rust" && categories == ["Rust"]
+        ));
+
+        app.focus = Focus::Image;
+        for code in ARROWS {
+            assert!(press(&app, code).is_none(), "{code:?}");
+        }
+    }
+
+    #[test]
+    fn download_popup_routes_its_keys() {
+        let mut app = app();
+        app.running_state = RunningState::ShowingDownloadPopup;
+        app.finished_download = Some((PathBuf::from("/tmp/Rust Book.png"), 1024));
+
+        assert!(matches!(
+            press(&app, KeyCode::Char('o')),
+            Some(Message::OpenDownloadFolder)
+        ));
+        assert!(matches!(
+            press(&app, KeyCode::Char('c')),
+            Some(Message::CopyDownloadPath)
+        ));
+        for code in [KeyCode::Enter, KeyCode::Esc, KeyCode::Char('q')] {
+            assert!(
+                matches!(press(&app, code), Some(Message::DismissDownloadPrompt)),
+                "{code:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn api_warning_takes_every_key() {
+        let mut app = app();
+        app.running_state = RunningState::BrowsingCategories;
+        app.categories = StatefulList::with_items(vec!["Rust".to_owned()]);
+        app.api_warning = Some(ApiVersion {
+            major: SUPPORTED_API_MAJOR + 1,
+            minor: 0,
+            patch: 0,
+        });
+
+        // Even the keys the categories handle only dismiss the warning
+        for code in [KeyCode::Char('q'), KeyCode::Enter, KeyCode::Down] {
+            assert!(
+                matches!(press(&app, code), Some(Message::DismissApiWarning)),
+                "{code:?}"
+            );
+        }
+
+        app.api_warning = None;
+        assert!(matches!(
+            press(&app, KeyCode::Char('q')),
+            Some(Message::Exit)
+        ));
+    }
+
+    fn draw(app: &mut App, width: u16, height: u16) -> Buffer {
+        let mut terminal = Terminal::new(backend::TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| view(app, f)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    fn rows(buf: &Buffer) -> Vec<String> {
+        (0..buf.area.height)
+            .map(|y| {
+                (0..buf.area.width)
+                    .map(|x| buf.get(x, y).symbol())
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn browsing_categories() -> App {
+        let mut app = app();
+        app.running_state = RunningState::BrowsingCategories;
+        app.prefetched_categories = PREFETCHED_CATEGORIES;
+        app.categories = StatefulList::with_items(vec!["Rust".to_owned(), "C++".to_owned()]);
+        app
+    }
+
+    #[test]
+    fn footer_grows_instead_of_cutting_hints_off() {
+        let hints = "Move Up <Up> Move Down <Down> Back <Left> Enter <Right> <Enter> Search <s> \
+                     </> Prefetch 3/10 <<> <>> Jump <C-p> Downloads <C-q> Help <?> Quit <q>";
+        let mut app = browsing_categories();
+
+        for (width, height) in [(72, 3), (90, 2), (120, 2)] {
+            let rows = rows(&draw(&mut app, width, 20));
+            // Everything below the bottom border of the lists
+            let footer = rows
+                .iter()
+                .skip(rows.iter().rposition(|x| x.starts_with('┗')).unwrap() + 1)
+                .collect::<Vec<_>>();
+            assert_eq!(footer.len(), height, "{width} columns");
+            assert!(
+                footer
+                    .iter()
+                    .flat_map(|x| x.split_whitespace())
+                    .eq(hints.split_whitespace()),
+                "{width} columns: {footer:#?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn too_small_window_keeps_the_state() {
+        let mut app = app();
+        app.running_state = RunningState::BrowsingImages;
+        app.install_list(
+            ListSource::Category("Rust".to_owned()),
+            (0..40)
+                .map(|i| book(&format!("Rust Book {i}"), "Rust"))
+                .collect(),
+            false,
+        );
+        app.images_list.state.select(Some(30));
+        app.image = Some(
+            new_image(
+                &SharedPicker::default(),
+                Some(book("Rust Book 30", "Rust")),
+                "Rust Book 30",
+                "Rust/Rust Book 30".to_owned(),
+                "Rust".to_owned(),
+                Bytes::from_static(PLACEHOLDER_IMAGE),
+                None,
+            )
+            .unwrap(),
+        );
+
+        let before = draw(&mut app, 100, 30);
+        let offset = app.images_list.state.offset();
+        assert!(rows(&before).concat().contains("Rust Book 30"));
+
+        let small = rows(&draw(&mut app, 60, 8));
+        assert!(small
+            .concat()
+            .contains("Window dimensions are too low: 8x60"));
+        assert!(!small.concat().contains("Rust Book"));
+
+        assert_eq!(draw(&mut app, 100, 30), before);
+        assert_eq!(app.running_state, RunningState::BrowsingImages);
+        assert_eq!(app.images_list.state.selected(), Some(30));
+        assert_eq!(app.images_list.state.offset(), offset);
+        assert_eq!(
+            app.image.as_ref().map(|x| x.search_id.as_str()),
+            Some("Rust/Rust Book 30")
+        );
+        app.tasks.abort_all();
+    }
+
+    #[test]
+    fn download_popup_wraps_the_path_at_the_smallest_width() {
+        let mut app = browsing_categories();
+        app.previous_running_state = RunningState::BrowsingCategories;
+        app.running_state = RunningState::ShowingDownloadPopup;
+        app.finished_download = Some((
+            PathBuf::from(
+                "/home/ferris/Downloads/anime-girls-holding-programming-books/Rust/\
+                 Ferris_Reading_The_Rust_Programming_Language.png",
+            ),
+            123_456,
+        ));
+
+        let rows = rows(&draw(&mut app, MIN_WINDOW_WIDTH, 20));
+        // The popup is centered in the image pane, right of the categories
+        let popup = rows[6..=12]
+            .iter()
+            .map(|x| x.chars().skip(22).collect::<String>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            popup,
+            [
+                "┏━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┓",
+                "┃              Download successful!              ┃",
+                "┃/home/ferris/Downloads/anime-girls-holding-progr┃",
+                "┃amming-books/Rust/Ferris_Reading_The_Rust_Progra┃",
+                "┃               mming_Language.png               ┃",
+                "┃                    120.6 KiB                   ┃",
+                "┗ <o> Open folder  <c> Copy path  <any> Dismiss ━┛",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn nasty_names_agree_across_list_favorites_and_downloads() {
+        let mut app = app();
+        app.running_state = RunningState::BrowsingImages;
+        let images = names::NASTY_NAMES
+            .iter()
+            .map(|(name, _)| book(name, "Rust"))
+            .collect::<Vec<_>>();
+        app.install_list(ListSource::Category("Rust".to_owned()), images, false);
+
+        for (i, (name, shown)) in names::NASTY_NAMES.iter().enumerate() {
+            assert_eq!(app.images_list.iter().nth(i), Some(*shown), "{name:?}");
+
+            app.images_list.state.select(Some(i));
+            app.handle_toggle_favorite();
+            assert!(app.favorites.names().any(|x| x == *shown), "{name:?}");
+
+            let image = new_image(
+                &SharedPicker::default(),
+                Some(book(name, "Rust")),
+                name,
+                format!("Rust/{name}"),
+                "Rust".to_owned(),
+                Bytes::from_static(PLACEHOLDER_IMAGE),
+                None,
+            )
+            .unwrap();
+            let path = app.download_path(&image);
+            let stem = path.file_stem().unwrap().to_str().unwrap();
+            assert_eq!(stem, *shown, "{name:?}");
+            assert!(!stem.ends_with(char::is_whitespace), "{name:?}");
+        }
+        app.tasks.abort_all();
+    }
+
+    #[tokio::test]
+    async fn next_and_previous_skip_images_that_failed_to_decode() {
+        let mut app = app();
+        app.running_state = RunningState::BrowsingImages;
+        app.install_list(
+            ListSource::Category("Rust".to_owned()),
+            vec![
+                book("Rust Book", "Rust"),
+                book("Rustonomicon", "Rust"),
+                book("Rust by Example", "Rust"),
+            ],
+            false,
+        );
+        app.failed_images
+            .insert("Rust/Rustonomicon".to_owned(), "truncated".to_owned());
+
+        app.update(Message::ShowNextImage).await;
+        assert_eq!(app.images_list.state.selected(), Some(2));
+        app.update(Message::ShowPreviousImage).await;
+        assert_eq!(app.images_list.state.selected(), Some(0));
+
+        // With every image failed, the selection doesn't move
+        for name in ["Rust Book", "Rust by Example"] {
+            app.failed_images
+                .insert(format!("Rust/{name}"), "truncated".to_owned());
+        }
+        app.update(Message::ShowNextImage).await;
+        assert_eq!(app.images_list.state.selected(), Some(0));
+        app.tasks.abort_all();
+    }
+
+    #[tokio::test]
+    async fn api_check_warns_only_about_newer_versions() {
+        let mut app = app();
+        let version = |major| ApiVersion {
+            major,
+            minor: 0,
+            patch: 0,
+        };
+
+        app.update(Message::ApiChecked(Some(version(SUPPORTED_API_MAJOR))))
+            .await;
+        assert_eq!(app.api_warning, None);
+        assert_eq!(app.api_warning_opened_at, None);
+
+        app.update(Message::ApiChecked(None)).await;
+        assert_eq!(app.api_warning, None);
+
+        let newer = version(SUPPORTED_API_MAJOR + 1);
+        app.update(Message::ApiChecked(Some(newer))).await;
+        assert_eq!(app.api_warning, Some(newer));
+        assert!(app.api_warning_opened_at.is_some());
+    }
 }
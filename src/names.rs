@@ -0,0 +1,47 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Returns the book name the way it's shown, used in file names and used as a
+/// key: NFC normalized, with every run of whitespace collapsed into a single
+/// space and none at either end. The API is still given the original name.
+pub fn normalize(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .nfc()
+        .collect()
+}
+
+/// Names like the ones found upstream, along with how they're shown.
+#[cfg(test)]
+pub const NASTY_NAMES: &[(&str, &str)] = &[
+    (
+        "Ferris  Reading  The Rust Book",
+        "Ferris Reading The Rust Book",
+    ),
+    ("  Yuki Holding SICP", "Yuki Holding SICP"),
+    ("Konata Holding K&R C   ", "Konata Holding K&R C"),
+    ("Tabs\tand\nnewlines", "Tabs and newlines"),
+    ("Aqua\u{a0}Holding\u{a0}Python", "Aqua Holding Python"),
+    (
+        "\u{3000}Hitori\u{3000}Holding Go\u{3000}",
+        "Hitori Holding Go",
+    ),
+    // NFD, the accent is its own code point
+    ("Cafe\u{301} Programming", "Caf\u{e9} Programming"),
+    // NFC, already composed
+    ("Caf\u{e9} Programming", "Caf\u{e9} Programming"),
+    (" \u{a0} \u{3000}Mixed \t\u{a0} Up\u{3000} ", "Mixed Up"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_nasty_names() {
+        for (name, shown) in NASTY_NAMES {
+            assert_eq!(normalize(name), *shown, "{name:?}");
+            assert_eq!(normalize(shown), *shown, "{name:?}");
+        }
+    }
+}
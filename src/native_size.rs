@@ -0,0 +1,129 @@
+use {
+    crate::{layout::centered_rect, tui::SharedPicker},
+    image::{imageops::FilterType, io::Reader as ImageReader, DynamicImage, GenericImageView},
+    ratatui::prelude::*,
+    ratatui_image::{protocol::StatefulProtocol, Resize, StatefulImage},
+    std::io::Cursor,
+};
+
+/// How many times an image can be magnified.
+const MAX_ZOOM: u32 = 8;
+
+/// An image shown at its real pixel size, panned around when it doesn't fit
+/// in the image pane.
+pub struct NativeImage {
+    pub search_id: String,
+    image: DynamicImage,
+    picker: SharedPicker,
+    // How many times the image is magnified, `1` for its real size
+    zoom: u32,
+    // Top left corner of the shown part of the image, in pixels
+    offset: (u32, u32),
+    // Size of the shown part of the image in pixels, as of the last render
+    view: (u32, u32),
+    // The shown part, cut again whenever the zoom, the offset or the view changes
+    state: Option<Box<dyn StatefulProtocol>>,
+    // The zoom, the offset and the view the shown part was cut at
+    cut: (u32, (u32, u32), (u32, u32)),
+}
+
+impl NativeImage {
+    /// Decodes the image data. `None` if it can't be decoded.
+    pub fn decode(search_id: String, data: &[u8], picker: SharedPicker) -> Option<NativeImage> {
+        let image = ImageReader::new(Cursor::new(data))
+            .with_guessed_format()
+            .ok()?
+            .decode()
+            .ok()?;
+        Some(NativeImage {
+            search_id,
+            image,
+            picker,
+            zoom: 1,
+            offset: (0, 0),
+            view: (0, 0),
+            state: None,
+            cut: (1, (0, 0), (0, 0)),
+        })
+    }
+
+    /// Moves the shown part of the image by a quarter of its size in the
+    /// direction of the signs of `x` and `y`.
+    pub fn pan(&mut self, x: i8, y: i8) {
+        let step = |offset: u32, view: u32, direction: i8| match direction {
+            ..=-1 => offset.saturating_sub(view / 4),
+            0 => offset,
+            1.. => offset + view / 4,
+        };
+        // NOTE: Panning past the edges is clamped on the next render, once the size
+        // of the image pane is known
+        self.offset = (
+            step(self.offset.0, self.view.0, x),
+            step(self.offset.1, self.view.1, y),
+        );
+    }
+
+    /// Doubles the magnification if `direction` is positive, halves it if
+    /// it's negative. Never shows the image smaller than its real size.
+    pub fn zoom(&mut self, direction: i8) {
+        self.zoom = match direction {
+            ..=-1 => (self.zoom / 2).max(1),
+            0 => self.zoom,
+            1.. => (self.zoom * 2).min(MAX_ZOOM),
+        };
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect) {
+        let font_size = self.picker.get().font_size;
+        let (font_width, font_height) =
+            (u32::from(font_size.0.max(1)), u32::from(font_size.1.max(1)));
+        let (width, height) = self.image.dimensions();
+
+        // Cells the image takes up at its zoomed size, as far as the pane allows
+        let columns = (width * self.zoom)
+            .div_ceil(font_width)
+            .min(u32::from(area.width));
+        let rows = (height * self.zoom)
+            .div_ceil(font_height)
+            .min(u32::from(area.height));
+        self.view = (
+            (columns * font_width / self.zoom).min(width),
+            (rows * font_height / self.zoom).min(height),
+        );
+        self.offset = (
+            self.offset.0.min(width - self.view.0),
+            self.offset.1.min(height - self.view.1),
+        );
+
+        if self.state.is_none() || self.cut != (self.zoom, self.offset, self.view) {
+            let mut part =
+                self.image
+                    .crop_imm(self.offset.0, self.offset.1, self.view.0, self.view.1);
+            // NOTE: Nearest keeps the pixels sharp, which is the point of zooming in
+            if self.zoom > 1 {
+                part = part.resize_exact(
+                    self.view.0 * self.zoom,
+                    self.view.1 * self.zoom,
+                    FilterType::Nearest,
+                );
+            }
+            self.state = Some(self.picker.new_resize_protocol(part));
+            self.cut = (self.zoom, self.offset, self.view);
+        }
+        // Impossible for this to explode as the state was just built
+        let state = self.state.as_mut().unwrap();
+
+        let area = centered_rect(
+            area,
+            Constraint::Length(columns as u16),
+            Constraint::Length(rows as u16),
+        );
+        // NOTE: The part is cut and zoomed to the size of the area, so cropping never
+        // scales it
+        f.render_stateful_widget(
+            StatefulImage::new(None).resize(Resize::Crop(None)),
+            area,
+            state,
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use {
+    color_eyre::{
+        eyre::{Context, ContextCompat},
+        Result, Section,
+    },
+    std::{collections::HashMap, fs, io, path::PathBuf},
+};
+
+/// The user's notes about images, keyed by image name and persisted across
+/// runs as a JSON object.
+#[derive(Debug, Default)]
+pub struct Notes {
+    notes: HashMap<String, String>,
+}
+
+impl Notes {
+    pub fn load() -> Result<Notes> {
+        let path = path()?;
+        let notes = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .wrap_err_with(|| format!("unable to parse the notes file: `{}`", path.display()))
+                .suggestion("fix or remove the notes file")?,
+            // No notes have been taken yet
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e).wrap_err("unable to read the notes file"),
+        };
+        Ok(Notes { notes })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> { self.notes.get(name).map(String::as_str) }
+
+    pub fn contains(&self, name: &str) -> bool { self.notes.contains_key(name) }
+
+    /// Saves the note about the image, an empty note removes it.
+    pub fn set(&mut self, name: &str, note: String) -> Result<()> {
+        if note.trim().is_empty() {
+            self.notes.remove(name);
+        } else {
+            self.notes.insert(name.to_owned(), note);
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = path()?;
+        // Impossible for the path to not have a parent as it's built from the data
+        // directory
+        fs::create_dir_all(path.parent().unwrap())
+            .wrap_err("unable to create the data directory")?;
+
+        // Impossible for this to explode as the notes are plain strings
+        fs::write(path, serde_json::to_string_pretty(&self.notes).unwrap())
+            .wrap_err("unable to write the notes file")
+            .suggestion("verify the permissions of your data directory")
+    }
+}
+
+fn path() -> Result<PathBuf> {
+    let mut path = dirs::data_dir().wrap_err("unable to locate data directory")?;
+    path.push("aghpb-tui");
+    path.push("notes.json");
+    Ok(path)
+}
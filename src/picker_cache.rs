@@ -0,0 +1,138 @@
+use {
+    color_eyre::{
+        eyre::{Context, ContextCompat},
+        Result, Section,
+    },
+    ratatui_image::picker::{Picker, ProtocolType},
+    std::{collections::HashMap, env, fs, io, path::PathBuf},
+};
+
+/// What probing the terminal found out, persisted across runs as one tab
+/// separated terminal key, protocol, font width, font height and tmux flag
+/// per line.
+#[derive(Debug, Clone, Copy)]
+pub struct Detected {
+    pub protocol: ProtocolType,
+    pub font_size: (u16, u16),
+    pub is_tmux: bool,
+}
+
+impl Detected {
+    pub fn of(picker: &Picker) -> Detected {
+        Detected {
+            protocol: picker.protocol_type,
+            font_size: picker.font_size,
+            is_tmux: picker.is_tmux,
+        }
+    }
+
+    pub fn to_picker(self) -> Picker {
+        let mut picker = Picker::new(self.font_size);
+        picker.protocol_type = self.protocol;
+        picker.is_tmux = self.is_tmux;
+        picker
+    }
+}
+
+/// Identifies the terminal the app runs in. A different terminal, or another
+/// window of the same one, gets detected again.
+pub fn key() -> String {
+    ["TERM", "TERM_PROGRAM", "WINDOWID", "KITTY_WINDOW_ID"]
+        .iter()
+        .map(|x| env::var(x).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Returns what was detected in the terminal last time, `None` if it wasn't
+/// or the entry doesn't make sense anymore. A broken cache isn't worth
+/// stopping over, it only means detecting again.
+pub fn load(key: &str) -> Option<Detected> {
+    let detected = read().ok()?.remove(key)?;
+    // NOTE: A zero sized font would divide by zero while rendering
+    (detected.font_size.0 > 0 && detected.font_size.1 > 0).then_some(detected)
+}
+
+pub fn save(key: &str, detected: Detected) -> Result<()> {
+    let mut entries = read().unwrap_or_default();
+    entries.insert(key.to_owned(), detected);
+    write(&entries)
+}
+
+/// Forgets what was detected in the terminal, so it's detected again on the
+/// next launch.
+pub fn remove(key: &str) -> Result<()> {
+    let mut entries = read().unwrap_or_default();
+    if entries.remove(key).is_some() {
+        write(&entries)?;
+    }
+    Ok(())
+}
+
+fn read() -> Result<HashMap<String, Detected>> {
+    let contents = match fs::read_to_string(path()?) {
+        Ok(contents) => contents,
+        // Nothing has been detected yet
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e).wrap_err("unable to read the terminal cache"),
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|x| {
+            let mut fields = x.split('\t');
+            let key = fields.next()?.to_owned();
+            let protocol = match fields.next()? {
+                "halfblocks" => ProtocolType::Halfblocks,
+                "sixel" => ProtocolType::Sixel,
+                "kitty" => ProtocolType::Kitty,
+                "iterm2" => ProtocolType::Iterm2,
+                _ => return None,
+            };
+            let font_size = (fields.next()?.parse().ok()?, fields.next()?.parse().ok()?);
+            let is_tmux = fields.next()?.parse().ok()?;
+            Some((
+                key,
+                Detected {
+                    protocol,
+                    font_size,
+                    is_tmux,
+                },
+            ))
+        })
+        .collect())
+}
+
+fn write(entries: &HashMap<String, Detected>) -> Result<()> {
+    let path = path()?;
+    // Impossible for the path to not have a parent as it's built from the data
+    // directory
+    fs::create_dir_all(path.parent().unwrap()).wrap_err("unable to create the data directory")?;
+
+    let mut lines = entries
+        .iter()
+        .map(|(key, x)| {
+            let protocol = match x.protocol {
+                ProtocolType::Halfblocks => "halfblocks",
+                ProtocolType::Sixel => "sixel",
+                ProtocolType::Kitty => "kitty",
+                ProtocolType::Iterm2 => "iterm2",
+            };
+            format!(
+                "{key}\t{protocol}\t{}\t{}\t{}",
+                x.font_size.0, x.font_size.1, x.is_tmux
+            )
+        })
+        .collect::<Vec<_>>();
+    // Keep the file stable between writes
+    lines.sort_unstable();
+    fs::write(path, lines.join("\n"))
+        .wrap_err("unable to write the terminal cache")
+        .suggestion("verify the permissions of your data directory")
+}
+
+fn path() -> Result<PathBuf> {
+    let mut path = dirs::data_dir().wrap_err("unable to locate data directory")?;
+    path.push("aghpb-tui");
+    path.push("terminal");
+    Ok(path)
+}
@@ -0,0 +1,68 @@
+use {
+    color_eyre::{
+        eyre::{Context, ContextCompat},
+        Result, Section,
+    },
+    std::{collections::HashSet, fs, io, path::PathBuf},
+};
+
+/// Identifiers of the images the user has already viewed, persisted across
+/// runs as one identifier per line.
+#[derive(Debug, Default)]
+pub struct Seen {
+    ids: HashSet<String>,
+}
+
+impl Seen {
+    pub fn load() -> Result<Seen> {
+        let ids = match fs::read_to_string(path()?) {
+            Ok(contents) => contents.lines().map(str::to_owned).collect(),
+            // Nothing has been seen yet
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(e).wrap_err("unable to read the seen images file"),
+        };
+        Ok(Seen { ids })
+    }
+
+    pub fn contains(&self, id: &str) -> bool { self.ids.contains(id) }
+
+    pub fn insert(&mut self, id: String) -> Result<()> {
+        if self.ids.insert(id) {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_all<'a>(&mut self, ids: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        let len = self.ids.len();
+        for id in ids {
+            self.ids.remove(id);
+        }
+        if self.ids.len() != len {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = path()?;
+        // Impossible for the path to not have a parent as it's built from the data
+        // directory
+        fs::create_dir_all(path.parent().unwrap())
+            .wrap_err("unable to create the data directory")?;
+
+        let mut ids = self.ids.iter().map(String::as_str).collect::<Vec<_>>();
+        // Keep the file stable between writes
+        ids.sort_unstable();
+        fs::write(path, ids.join("\n"))
+            .wrap_err("unable to write the seen images file")
+            .suggestion("verify the permissions of your data directory")
+    }
+}
+
+fn path() -> Result<PathBuf> {
+    let mut path = dirs::data_dir().wrap_err("unable to locate data directory")?;
+    path.push("aghpb-tui");
+    path.push("seen");
+    Ok(path)
+}
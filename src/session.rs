@@ -0,0 +1,58 @@
+use {
+    crate::stateful_list::StatefulListState,
+    color_eyre::{
+        eyre::{Context, ContextCompat},
+        Result, Section,
+    },
+    serde::{Deserialize, Serialize},
+    std::{fs, io, path::PathBuf},
+};
+
+/// Where the user left off, saved on exit so the next run can pick up from
+/// there.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub categories: Option<StatefulListState>,
+    // Category the image list belongs to, the selection only applies to it
+    pub category: Option<String>,
+    pub images: Option<StatefulListState>,
+    // Whether searches started from a category look through every category
+    // instead of just that one
+    pub search_all: bool,
+}
+
+impl Session {
+    pub fn load() -> Result<Session> {
+        let path = path()?;
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            // Nothing to resume on the first run
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Session::default()),
+            Err(e) => return Err(e).wrap_err("unable to read the session file"),
+        };
+
+        // NOTE: A broken session isn't worth refusing to start over
+        Ok(toml::from_str(&contents).unwrap_or_default())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = path()?;
+        // Impossible for the path to not have a parent as it's built from the data
+        // directory
+        fs::create_dir_all(path.parent().unwrap())
+            .wrap_err("unable to create the data directory")?;
+
+        // Impossible for this to explode as the session only holds plain data
+        fs::write(path, toml::to_string(self).unwrap())
+            .wrap_err("unable to write the session file")
+            .suggestion("verify the permissions of your data directory")
+    }
+}
+
+fn path() -> Result<PathBuf> {
+    let mut path = dirs::data_dir().wrap_err("unable to locate data directory")?;
+    path.push("aghpb-tui");
+    path.push("session.toml");
+    Ok(path)
+}
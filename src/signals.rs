@@ -0,0 +1,35 @@
+use {color_eyre::Result, tokio::sync::oneshot};
+
+/// Listens for the signals asking the app to quit. The returned receiver gets
+/// a value once one of them arrives.
+#[cfg(unix)]
+pub fn shutdown() -> Result<oneshot::Receiver<()>> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let (tx, rx) = oneshot::channel();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = interrupt.recv() => {},
+            _ = terminate.recv() => {},
+        }
+        // The receiver is only gone once the app is already quitting
+        let _ = tx.send(());
+    });
+    Ok(rx)
+}
+
+/// Listens for the signals asking the app to quit. The returned receiver gets
+/// a value once one of them arrives.
+#[cfg(windows)]
+pub fn shutdown() -> Result<oneshot::Receiver<()>> {
+    let (tx, rx) = oneshot::channel();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            // The receiver is only gone once the app is already quitting
+            let _ = tx.send(());
+        }
+    });
+    Ok(rx)
+}
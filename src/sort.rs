@@ -0,0 +1,58 @@
+use {crate::names, aghpb::BookData, std::cmp::Reverse};
+
+/// What the image list is sorted by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    // The order the list came in, by name for categories and by relevance for
+    // searches
+    #[default]
+    Source,
+    Name,
+    // Books without an author go last, by name
+    Author,
+    // Newest first
+    DateAdded,
+}
+
+impl SortBy {
+    pub fn next(self) -> SortBy {
+        match self {
+            SortBy::Source => SortBy::Name,
+            SortBy::Name => SortBy::Author,
+            SortBy::Author => SortBy::DateAdded,
+            SortBy::DateAdded => SortBy::Source,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortBy::Source => "default order",
+            SortBy::Name => "name",
+            SortBy::Author => "author",
+            SortBy::DateAdded => "date added",
+        }
+    }
+
+    /// Sorts the items by the book each of them is about. Ties are broken by
+    /// name, and the sort is stable, so [`SortBy::Source`] leaves the items as
+    /// they are.
+    pub fn sort<T>(self, items: &mut [T], book: impl Fn(&T) -> &BookData) {
+        // PERF: Normalizing is expensive enough to warrant `cached_key`
+        match self {
+            SortBy::Source => {},
+            SortBy::Name => items.sort_by_cached_key(|x| names::normalize(&book(x).name)),
+            SortBy::Author => items.sort_by_cached_key(|x| {
+                let book = book(x);
+                (
+                    book.commit_author.is_empty(),
+                    book.commit_author.to_lowercase(),
+                    names::normalize(&book.name),
+                )
+            }),
+            SortBy::DateAdded => items.sort_by_cached_key(|x| {
+                let book = book(x);
+                (Reverse(book.date_added), names::normalize(&book.name))
+            }),
+        }
+    }
+}
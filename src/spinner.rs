@@ -0,0 +1,67 @@
+use {
+    serde::{Deserialize, Serialize},
+    std::time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The animation shown next to what's loading, either one of the built-in
+/// ones by name or a list of frames.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Spinner {
+    Named(SpinnerName),
+    Custom(Vec<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpinnerName {
+    // Dots filling up, like an ellipsis
+    Dots,
+    Line,
+    Braille,
+    // No animation at all
+    None,
+}
+
+impl Default for Spinner {
+    fn default() -> Self { Spinner::Named(SpinnerName::Braille) }
+}
+
+impl Spinner {
+    fn frames(&self) -> Vec<&str> {
+        match self {
+            Spinner::Named(SpinnerName::Dots) => vec!["   ", ".  ", ".. ", "..."],
+            Spinner::Named(SpinnerName::Line) => vec!["-", "\\", "|", "/"],
+            Spinner::Named(SpinnerName::Braille) => {
+                vec!["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]
+            },
+            Spinner::Named(SpinnerName::None) => Vec::new(),
+            Spinner::Custom(frames) => frames.iter().map(String::as_str).collect(),
+        }
+    }
+
+    /// Returns the frame to show now, moving on to the next one every
+    /// `interval_ms`. Empty without any frames.
+    pub fn frame(&self, interval_ms: u64) -> &str {
+        let frames = self.frames();
+        if frames.is_empty() {
+            return "";
+        }
+        // NOTE: Going by the clock keeps every spinner on screen in step without
+        // storing anything
+        let step = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+            / u128::from(interval_ms.max(1));
+        frames[(step % frames.len() as u128) as usize]
+    }
+
+    /// Puts the current frame in front of the text, if there's one.
+    pub fn with_text(&self, interval_ms: u64, text: &str) -> String {
+        match self.frame(interval_ms) {
+            "" => text.to_owned(),
+            frame => format!("{frame} {text}"),
+        }
+    }
+}
@@ -1,9 +1,35 @@
-use ratatui::widgets::*;
+use {
+    crate::config::ListOverflow,
+    ratatui::{prelude::*, widgets::*},
+    serde::{Deserialize, Serialize},
+    textwrap::{Options, WordSeparator, WordSplitter},
+};
 
-#[derive(Debug, Default)]
+/// The part of a [`StatefulList`] worth keeping across runs, the items
+/// themselves are fetched again.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct StatefulListState {
+    pub selected: Option<usize>,
+    pub items_len: usize,
+}
+
+#[derive(Debug)]
 pub struct StatefulList {
     pub state: ListState,
-    pub items: Vec<String>,
+    items: Vec<String>,
+    // How items too long for the list are wrapped, the width is replaced by the
+    // width of the list when it's drawn
+    wrap_options: Options<'static>,
+}
+
+impl Default for StatefulList {
+    fn default() -> Self {
+        StatefulList {
+            state: ListState::default(),
+            items: Vec::new(),
+            wrap_options: StatefulList::default_wrap_options(),
+        }
+    }
 }
 
 impl StatefulList {
@@ -11,19 +37,196 @@ impl StatefulList {
         StatefulList {
             state: ListState::default().with_selected(Some(0)),
             items,
+            ..Default::default()
         }
     }
 
-    pub fn get_list<'a>(&self, width: u16) -> List<'a> {
-        let rows = self
-            .items
-            .iter()
-            // `- 2` to account for the block border
-            .map(|x| textwrap::fill(x, width as usize - 2));
+    pub fn with_capacity(capacity: usize) -> StatefulList {
+        StatefulList {
+            items: Vec::with_capacity(capacity),
+            ..Default::default()
+        }
+    }
+
+    /// Wraps items with `options` instead of
+    /// [`StatefulList::default_wrap_options`].
+    pub fn with_wrap_options(self, options: Options<'static>) -> StatefulList {
+        StatefulList {
+            wrap_options: options,
+            ..self
+        }
+    }
+
+    /// Breaks lines between words only, so names like
+    /// "Rust-Programming-Language-2018" aren't split at their hyphens.
+    pub fn default_wrap_options() -> Options<'static> {
+        Options::new(0)
+            .word_separator(WordSeparator::UnicodeBreakProperties)
+            .word_splitter(WordSplitter::NoHyphenation)
+    }
+
+    pub fn push(&mut self, item: String) {
+        self.items.push(item);
+
+        // Lists start out with their first item selected
+        if self.state.selected().is_none() {
+            self.state.select(Some(0));
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<String> { self.items.pop() }
+
+    pub fn remove(&mut self, i: usize) -> String { self.items.remove(i) }
+
+    pub fn swap(&mut self, i: usize, j: usize) { self.items.swap(i, j); }
+
+    pub fn get(&self, i: usize) -> Option<&str> { self.items.get(i).map(String::as_str) }
+
+    pub fn len(&self) -> usize { self.items.len() }
+
+    pub fn is_empty(&self) -> bool { self.items.is_empty() }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> { self.items.iter().map(String::as_str) }
+
+    pub fn get_list<'a>(&self, width: u16, overflow: ListOverflow) -> List<'a> {
+        self.get_decorated_list(width, overflow, |_| ("", Style::default()))
+    }
+
+    /// Same as [`StatefulList::get_list`], but prefixes each row with the
+    /// marker and styles it with the style returned for its index.
+    pub fn get_decorated_list<'a>(
+        &self,
+        width: u16,
+        overflow: ListOverflow,
+        decorate: impl Fn(usize) -> (&'static str, Style),
+    ) -> List<'a> {
+        List::new(self.rows(width, overflow, decorate))
+    }
+
+    /// Same as [`StatefulList::get_decorated_list`], but starts with an italic
+    /// "No selection" row while nothing is selected.
+    pub fn get_selection_list<'a>(
+        &self,
+        width: u16,
+        overflow: ListOverflow,
+        decorate: impl Fn(usize) -> (&'static str, Style),
+    ) -> List<'a> {
+        let mut rows = self.rows(width, overflow, decorate);
+        if self.state.selected().is_none() {
+            rows.insert(0, ListItem::new("No selection".italic()));
+        }
         List::new(rows)
     }
 
+    fn rows<'a>(
+        &self,
+        width: u16,
+        overflow: ListOverflow,
+        decorate: impl Fn(usize) -> (&'static str, Style),
+    ) -> Vec<ListItem<'a>> {
+        // `- 2` to account for the block border
+        let width = usize::from(width.saturating_sub(2)).max(1);
+        self.items
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let (marker, style) = decorate(i);
+                let text = format!("{marker}{x}");
+                let text = match overflow {
+                    ListOverflow::Wrap => {
+                        textwrap::fill(&text, self.wrap_options.clone().width(width))
+                    },
+                    ListOverflow::Truncate => truncate(&text, width),
+                };
+                ListItem::new(text).style(style)
+            })
+            .collect()
+    }
+
+    pub fn deselect(&mut self) { self.state.select(None); }
+
+    pub fn save_state(&self) -> StatefulListState {
+        StatefulListState {
+            selected: self.state.selected(),
+            items_len: self.items.len(),
+        }
+    }
+
+    /// Selects the saved selection again, as long as the list is still long
+    /// enough for it.
+    pub fn restore_state(&mut self, state: StatefulListState) {
+        if let Some(selected) = state.selected.filter(|x| *x < self.items.len()) {
+            self.state.select(Some(selected));
+        }
+    }
+
+    /// Returns the index of the next item containing `query`, ignoring case.
+    /// The search starts after `from` and wraps around to the start, `from`
+    /// itself is checked last.
+    pub fn search_wrap(&self, query: &str, from: usize) -> Option<usize> {
+        let len = self.items.len();
+        self.find_in((1..=len).map(|i| (from + i) % len), query)
+    }
+
+    /// Same as [`StatefulList::search_wrap`], but searches backwards and wraps
+    /// around to the end.
+    pub fn search_wrap_backward(&self, query: &str, from: usize) -> Option<usize> {
+        let len = self.items.len();
+        self.find_in((1..=len).map(|i| (from % len + len - i) % len), query)
+    }
+
+    /// Selects the next item after the selected one starting with `prefix`,
+    /// ignoring case, wrapping around to the first one. Returns whether there
+    /// was one.
+    pub fn select_by_prefix(&mut self, prefix: &str) -> bool {
+        let found = self.find_all_prefixed(prefix);
+        let selected = self.state.selected();
+        let next = found
+            .iter()
+            .find(|i| selected.is_some_and(|x| **i > x))
+            .or(found.first());
+        if next.is_some() {
+            self.state.select(next.copied());
+        }
+        next.is_some()
+    }
+
+    /// Returns the indices of every item containing `query`, ignoring case.
+    pub fn find_all(&self, query: &str) -> Vec<usize> {
+        let query = query.to_lowercase();
+        self.positions(|x| x.to_lowercase().contains(&query))
+    }
+
+    /// Returns the indices of every item starting with `prefix`, ignoring
+    /// case.
+    pub fn find_all_prefixed(&self, prefix: &str) -> Vec<usize> {
+        let prefix = prefix.to_lowercase();
+        self.positions(|x| x.to_lowercase().starts_with(&prefix))
+    }
+
+    /// Returns the indices of every item `pattern` matches.
+    #[cfg(feature = "regex")]
+    pub fn find_all_regex(&self, pattern: &regex::Regex) -> Vec<usize> {
+        self.positions(|x| pattern.is_match(x))
+    }
+
+    fn positions(&self, predicate: impl Fn(&str) -> bool) -> Vec<usize> {
+        self.iter()
+            .enumerate()
+            .filter(|(_, x)| predicate(x))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn find_in(&self, mut indices: impl Iterator<Item = usize>, query: &str) -> Option<usize> {
+        let query = query.to_lowercase();
+        indices.find(|i| self.items[*i].to_lowercase().contains(&query))
+    }
+
     pub fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i >= self.items.len() - 1 {
@@ -38,6 +241,9 @@ impl StatefulList {
     }
 
     pub fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -51,3 +257,99 @@ impl StatefulList {
         self.state.select(Some(i));
     }
 }
+
+/// Cuts the text down to `width` columns, ending it with an ellipsis if
+/// anything was cut.
+fn truncate(text: &str, width: usize) -> String {
+    if textwrap::core::display_width(text) <= width {
+        return text.to_owned();
+    }
+
+    let mut truncated = String::new();
+    // The ellipsis takes up a column too
+    let mut used = 1;
+    for c in text.chars() {
+        used += textwrap::core::display_width(c.encode_utf8(&mut [0; 4]));
+        if used > width {
+            break;
+        }
+        truncated.push(c);
+    }
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(items: &[&str]) -> StatefulList {
+        StatefulList::with_items(items.iter().map(|x| (*x).to_owned()).collect())
+    }
+
+    #[test]
+    fn search_wrap_empty_list() {
+        let list = list(&[]);
+        assert_eq!(list.search_wrap("rust", 0), None);
+        assert_eq!(list.search_wrap_backward("rust", 0), None);
+    }
+
+    #[test]
+    fn search_wrap_no_match() {
+        let list = list(&["Go", "Python", "Zig"]);
+        assert_eq!(list.search_wrap("rust", 0), None);
+        assert_eq!(list.search_wrap_backward("rust", 0), None);
+    }
+
+    #[test]
+    fn search_wrap_one_match() {
+        let list = list(&["Go", "Rust", "Zig"]);
+        assert_eq!(list.search_wrap("rust", 0), Some(1));
+        assert_eq!(list.search_wrap_backward("RUST", 0), Some(1));
+        // The item searched from is checked last
+        assert_eq!(list.search_wrap("rust", 1), Some(1));
+        assert_eq!(list.search_wrap_backward("rust", 1), Some(1));
+    }
+
+    #[test]
+    fn search_wrap_several_matches() {
+        let list = list(&["Rust 1", "Go", "Rust 2", "Rust 3"]);
+        assert_eq!(list.search_wrap("rust", 0), Some(2));
+        assert_eq!(list.search_wrap("rust", 2), Some(3));
+        assert_eq!(list.search_wrap_backward("rust", 3), Some(2));
+        assert_eq!(list.search_wrap_backward("rust", 2), Some(0));
+    }
+
+    #[test]
+    fn search_wrap_from_last_index_wraps_to_start() {
+        let list = list(&["Rust", "Go", "Zig"]);
+        assert_eq!(list.search_wrap("rust", 2), Some(0));
+    }
+
+    #[test]
+    fn search_wrap_backward_wraps_to_end() {
+        let list = list(&["Go", "Zig", "Rust"]);
+        assert_eq!(list.search_wrap_backward("rust", 0), Some(2));
+        assert_eq!(list.search_wrap_backward("go", 0), Some(0));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn find_all_regex_matches() {
+        let list = list(&["Rust-2018", "Go", "Rust-2021", "Rustonomicon"]);
+        let pattern = regex::Regex::new(r"^Rust-\d+$").unwrap();
+        assert_eq!(list.find_all_regex(&pattern), [0, 2]);
+    }
+
+    #[test]
+    fn push_selects_the_first_item() {
+        let mut list = StatefulList::with_capacity(2);
+        assert_eq!(list.state.selected(), None);
+        list.push("Rust".to_owned());
+        assert_eq!(list.state.selected(), Some(0));
+        // Later items leave the selection alone
+        list.push("Go".to_owned());
+        assert_eq!(list.state.selected(), Some(0));
+        assert!(list.items.capacity() >= 2);
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+
+/// Splits category names into tags, so "Data Science" and "Computer Science"
+/// both show up under "Science". Returns every tag with the categories
+/// carrying it, sorted case insensitively. Tags differing only in case are
+/// merged.
+pub fn from_categories<'a>(
+    categories: impl IntoIterator<Item = &'a str>,
+) -> Vec<(String, Vec<String>)> {
+    let mut tags: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+
+    for category in categories {
+        for word in words(category) {
+            let (_, tagged) = tags
+                .entry(word.to_lowercase())
+                .or_insert_with(|| (word.to_owned(), Vec::new()));
+            if !tagged.iter().any(|x| x == category) {
+                tagged.push(category.to_owned());
+            }
+        }
+    }
+
+    tags.into_values().collect()
+}
+
+/// Returns the categories carrying the tag.
+pub fn categories_with<'a>(
+    tag: &str,
+    categories: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let tag = tag.to_lowercase();
+    categories
+        .into_iter()
+        .filter(|x| words(x).any(|x| x.to_lowercase() == tag))
+        .map(str::to_owned)
+        .collect()
+}
+
+// NOTE: Only whitespace, dashes and underscores separate words, so "C++" and
+// "C#" stay tags of their own
+fn words(category: &str) -> impl Iterator<Item = &str> {
+    category
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .filter(|x| !x.is_empty())
+}
@@ -0,0 +1,33 @@
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+/// How long a toast stays on screen.
+const TOAST_DURATION: Duration = Duration::from_secs(3);
+
+/// A short lived notification shown in the corner of the screen.
+#[derive(Debug)]
+pub struct Toast {
+    pub message: String,
+    shown_at: Instant,
+}
+
+impl Toast {
+    pub fn new(message: impl Into<String>) -> Toast {
+        Toast {
+            message: message.into(),
+            shown_at: Instant::now(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool { self.shown_at.elapsed() >= TOAST_DURATION }
+}
+
+/// Rings the terminal bell, for events worth hearing about while the
+/// terminal is in the background.
+pub fn bell() -> io::Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(b"\x07")?;
+    stdout.flush()
+}
@@ -0,0 +1,69 @@
+use {
+    crate::RunningState,
+    ratatui::{buffer::Cell, prelude::*},
+    std::time::Duration,
+};
+
+/// How many frames a transition is drawn over.
+const FRAMES: u8 = 3;
+/// How long each frame of a transition is shown.
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(40);
+
+/// The way the new list comes in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    // From the right, going into a category
+    Forward,
+    // From the left, going back to the categories
+    Backward,
+}
+
+/// A slide of the list pane between the categories and the images.
+#[derive(Debug, Clone, Copy)]
+pub struct Transition {
+    pub from_state: RunningState,
+    pub frame: u8,
+    pub direction: Direction,
+}
+
+impl Transition {
+    /// Returns the transition from `from_state` to `to_state`, `None` if it
+    /// isn't animated.
+    pub fn between(from_state: RunningState, to_state: RunningState) -> Option<Transition> {
+        let direction = match (from_state, to_state) {
+            (RunningState::BrowsingCategories, RunningState::BrowsingImages) => Direction::Forward,
+            (RunningState::BrowsingImages, RunningState::BrowsingCategories) => Direction::Backward,
+            _ => return None,
+        };
+        Some(Transition {
+            from_state,
+            frame: 0,
+            direction,
+        })
+    }
+
+    /// Shifts what was drawn in `area` sideways, less with every frame, so
+    /// it slides into place. Returns whether this was the last frame.
+    pub fn apply(&mut self, area: Rect, buf: &mut Buffer) -> bool {
+        let offset = area.width * u16::from(FRAMES - self.frame) / u16::from(FRAMES + 1);
+        for y in area.top()..area.bottom() {
+            let row = (area.left()..area.right())
+                .map(|x| buf.get(x, y).clone())
+                .collect::<Vec<_>>();
+            for (i, x) in (area.left()..area.right()).enumerate() {
+                // The part of the row the list hasn't slid over yet is left empty
+                let source = match self.direction {
+                    Direction::Forward => i.checked_sub(usize::from(offset)),
+                    Direction::Backward => Some(i + usize::from(offset)),
+                };
+                *buf.get_mut(x, y) = source
+                    .and_then(|i| row.get(i))
+                    .cloned()
+                    .unwrap_or_else(Cell::default);
+            }
+        }
+
+        self.frame += 1;
+        self.frame == FRAMES
+    }
+}
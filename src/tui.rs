@@ -1,6 +1,9 @@
 use {
+    crate::picker_cache::{self, Detected},
+    image::DynamicImage,
     ratatui::{
         crossterm::{
+            event::{DisableFocusChange, EnableFocusChange},
             terminal::{
                 disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
             },
@@ -8,19 +11,243 @@ use {
         },
         prelude::*,
     },
-    std::io::{self, stdout, Stdout},
+    ratatui_image::{
+        picker::{Picker, ProtocolType},
+        protocol::StatefulProtocol,
+    },
+    std::{
+        env,
+        io::{self, stdout, Stdout, Write},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex, PoisonError,
+        },
+        thread,
+    },
 };
 
-pub type Tui = Terminal<CrosstermBackend<Stdout>>;
+pub type Tui = Terminal<CrosstermBackend<CountingStdout>>;
+
+/// Bytes drawing has sent to the terminal so far.
+static WRITTEN: AtomicU64 = AtomicU64::new(0);
+
+/// Stdout counting what's written through it, to tell how much drawing sends
+/// to the terminal. Image protocols are the bulk of it.
+pub struct CountingStdout(Stdout);
+
+impl Write for CountingStdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.0.write(buf)?;
+        WRITTEN.fetch_add(written as u64, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> { self.0.flush() }
+}
+
+/// Returns how many bytes drawing has sent to the terminal so far.
+pub fn bytes_written() -> u64 { WRITTEN.load(Ordering::Relaxed) }
 
 pub fn init() -> io::Result<Tui> {
     stdout().execute(EnterAlternateScreen)?;
+    // NOTE: Terminals that don't report focus changes ignore this
+    stdout().execute(EnableFocusChange)?;
     enable_raw_mode()?;
-    Terminal::new(CrosstermBackend::new(stdout()))
+    // Save the window title on the terminal's title stack, for `restore`
+    #[cfg(unix)]
+    write_escape("\x1b[22;0t");
+    Terminal::new(CrosstermBackend::new(CountingStdout(stdout())))
 }
 
 pub fn restore() -> io::Result<()> {
+    #[cfg(unix)]
+    write_escape("\x1b[23;0t");
+    stdout().execute(DisableFocusChange)?;
     stdout().execute(LeaveAlternateScreen)?;
     disable_raw_mode()?;
     Ok(())
 }
+
+/// Shows `title` as the terminal window title, which screen readers read out
+/// as well. Does nothing on Windows.
+pub fn set_terminal_title(title: &str) {
+    // NOTE: A control character would end the sequence early
+    let title = title.replace(char::is_control, "");
+    #[cfg(unix)]
+    write_escape(&format!("\x1b]2;aghpb-tui: {title}\x07"));
+    #[cfg(not(unix))]
+    let _ = title;
+}
+
+#[cfg(unix)]
+fn write_escape(escape: &str) {
+    let mut stdout = stdout();
+    // NOTE: Terminals without a title to set ignore these, so a failed write isn't
+    // worth interrupting the user over
+    let _ = stdout
+        .write_all(escape.as_bytes())
+        .and_then(|()| stdout.flush());
+}
+
+/// Font size assumed when the terminal doesn't report its own. Off by about
+/// half on high density screens.
+const FALLBACK_FONT_SIZE: (u16, u16) = (7, 14);
+
+/// Returns the picker detected in this terminal on a previous run, checking
+/// it again in the background, or probes the terminal for its graphics
+/// protocol and font size when there's none or `redetect` is set. Probing can
+/// take a while, so it's only done once at startup. `font_size` replaces
+/// the detected one. Also returns whether the font size is only a guess.
+pub fn init_image_picker(redetect: bool, font_size: Option<(u16, u16)>) -> (SharedPicker, bool) {
+    let key = picker_cache::key();
+
+    if let Some(detected) = picker_cache::load(&key).filter(|_| !redetect) {
+        // NOTE: Drawing with a protocol the terminal doesn't speak fills it with
+        // garbage, halfblocks always work
+        let mismatch = env_protocol().is_some_and(|x| x != detected.protocol);
+        let mut picker = if mismatch {
+            Picker::new(detected.font_size)
+        } else {
+            detected.to_picker()
+        };
+        if let Some(font_size) = font_size {
+            picker.font_size = font_size;
+        }
+        let picker = SharedPicker::new(picker);
+
+        let shared = picker.clone();
+        let overridden = font_size.is_some();
+        thread::spawn(move || verify_image_picker(&key, detected, mismatch, overridden, &shared));
+        return (picker, false);
+    }
+
+    let (mut picker, guessed) = detect_image_picker();
+    // NOTE: A guessed font size isn't cached, so the terminal is probed again next
+    // time instead of the guess sticking. Failing to cache only means the same.
+    if !guessed {
+        let _ = picker_cache::save(&key, Detected::of(&picker));
+    }
+    if let Some(font_size) = font_size {
+        picker.font_size = font_size;
+    }
+    (SharedPicker::new(picker), guessed && font_size.is_none())
+}
+
+/// Probes the terminal, returning whether its font size had to be guessed.
+fn detect_image_picker() -> (Picker, bool) {
+    // NOTE: Windows doesn't support `termios`
+    #[cfg(windows)]
+    let (mut picker, guessed) = (Picker::new(FALLBACK_FONT_SIZE), true);
+    #[cfg(unix)]
+    let (mut picker, guessed) = match Picker::from_termios() {
+        Ok(picker) => (picker, false),
+        Err(_) => (Picker::new(FALLBACK_FONT_SIZE), true),
+    };
+
+    picker.guess_protocol();
+
+    // HACK: Protocol guesser doesn't pickup sixel for xterm in the app for some
+    // reason
+    if let Ok(term) = env::var("TERM") {
+        if &term == "xterm" {
+            picker.protocol_type = ProtocolType::Sixel;
+        }
+    }
+    (picker, guessed)
+}
+
+/// Checks the cached detection against the terminal without querying it, as
+/// the app is reading its input by then. A changed font size is picked up
+/// right away, a changed protocol on the next launch. A font size that's
+/// `overridden` stays as it is on screen, only the cache is updated.
+fn verify_image_picker(
+    key: &str,
+    detected: Detected,
+    mismatch: bool,
+    overridden: bool,
+    picker: &SharedPicker,
+) {
+    if mismatch {
+        let _ = picker_cache::remove(key);
+        return;
+    }
+
+    #[cfg(unix)]
+    if let Ok(font_size) = Picker::from_termios().map(|x| x.font_size) {
+        if font_size != detected.font_size {
+            if !overridden {
+                picker.set_font_size(font_size);
+            }
+            let _ = picker_cache::save(
+                key,
+                Detected {
+                    font_size,
+                    ..detected
+                },
+            );
+        }
+    }
+    #[cfg(windows)]
+    let _ = (picker, overridden);
+}
+
+/// The protocol the environment variables give away, the same way
+/// [`Picker::guess_protocol`] reads them. `None` if only querying the
+/// terminal would tell.
+fn env_protocol() -> Option<ProtocolType> {
+    let term = env::var("TERM").unwrap_or_default();
+    let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+    let lc_terminal = env::var("LC_TERMINAL").unwrap_or_default();
+
+    if term == "mlterm" || term == "yaft-256color" || term == "xterm" {
+        Some(ProtocolType::Sixel)
+    } else if term.contains("kitty") {
+        Some(ProtocolType::Kitty)
+    } else if term_program == "MacTerm" {
+        Some(ProtocolType::Sixel)
+    } else if term_program.contains("iTerm")
+        || term_program.contains("WezTerm")
+        || lc_terminal.contains("iTerm")
+    {
+        Some(ProtocolType::Iterm2)
+    } else {
+        None
+    }
+}
+
+/// The picker from [`init_image_picker`], shared between everything that
+/// builds images so no two Kitty images get the same id.
+#[derive(Clone)]
+pub struct SharedPicker(Arc<Mutex<Picker>>);
+
+impl SharedPicker {
+    pub fn new(picker: Picker) -> SharedPicker { SharedPicker(Arc::new(Mutex::new(picker))) }
+
+    /// A copy of the picker, for reading its protocol and font size.
+    pub fn get(&self) -> Picker { *self.0.lock().unwrap_or_else(PoisonError::into_inner) }
+
+    pub fn set_protocol_type(&self, protocol_type: ProtocolType) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .protocol_type = protocol_type;
+    }
+
+    pub fn set_font_size(&self, font_size: (u16, u16)) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .font_size = font_size;
+    }
+
+    pub fn new_resize_protocol(&self, image: DynamicImage) -> Box<dyn StatefulProtocol> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .new_resize_protocol(image)
+    }
+}
+
+impl Default for SharedPicker {
+    fn default() -> Self { SharedPicker::new(Picker::new(FALLBACK_FONT_SIZE)) }
+}
@@ -0,0 +1,230 @@
+use {
+    crate::{layout::centered_rect, Message},
+    ratatui::{
+        crossterm::event::{KeyCode, KeyEvent},
+        prelude::*,
+        widgets::*,
+    },
+};
+
+/// A key the popup acts on, shown in the action bar along its bottom border.
+struct Action<'a> {
+    key: KeyCode,
+    label: &'a str,
+    message: Message,
+}
+
+/// A popup with a row of actions along its bottom border. It's built again
+/// both to be drawn and to route a key, so the actions it shows are always
+/// the ones the keys trigger.
+pub struct Popup<'a> {
+    title: Line<'a>,
+    body: Text<'a>,
+    actions: Vec<Action<'a>>,
+    // What any other key does, shown as the last action
+    fallback: Option<(&'a str, Message)>,
+    border_type: BorderType,
+}
+
+impl<'a> Popup<'a> {
+    pub fn new(title: impl Into<Line<'a>>, body: impl Into<Text<'a>>) -> Popup<'a> {
+        Popup {
+            title: title.into(),
+            body: body.into(),
+            actions: Vec::new(),
+            fallback: None,
+            border_type: BorderType::Thick,
+        }
+    }
+
+    pub fn action(mut self, key: KeyCode, label: &'a str, message: Message) -> Popup<'a> {
+        self.actions.push(Action {
+            key,
+            label,
+            message,
+        });
+        self
+    }
+
+    /// Sends `message` for every key without an action of its own.
+    pub fn fallback(mut self, label: &'a str, message: Message) -> Popup<'a> {
+        self.fallback = Some((label, message));
+        self
+    }
+
+    pub fn border_type(self, border_type: BorderType) -> Popup<'a> {
+        Popup {
+            border_type,
+            ..self
+        }
+    }
+
+    /// Returns the message of the action bound to the key.
+    pub fn handle_key(self, key: KeyEvent) -> Option<Message> {
+        self.actions
+            .into_iter()
+            .find(|x| x.key == key.code)
+            .map(|x| x.message)
+            .or(self.fallback.map(|(_, message)| message))
+    }
+
+    /// Returns the area the popup takes up in the middle of `area`. Fits the
+    /// longest line if `area` allows, the lines wrap otherwise.
+    pub fn area(&self, area: Rect) -> Rect {
+        // `+ 2` to account for the block border
+        let width = self
+            .body
+            .lines
+            .iter()
+            .map(Line::width)
+            .chain([self.action_bar().width(), self.title.width()])
+            .max()
+            .unwrap_or_default() as u16
+            + 2;
+        let width = width.min(area.width);
+        let height = self
+            .body
+            .lines
+            .iter()
+            .map(|x| {
+                let line = x.spans.iter().map(|x| &*x.content).collect::<String>();
+                textwrap::wrap(&line, usize::from(width.saturating_sub(2)).max(1)).len() as u16
+            })
+            .sum::<u16>()
+            + 2;
+        centered_rect(
+            area,
+            Constraint::Length(width),
+            Constraint::Length(height.min(area.height)),
+        )
+    }
+
+    fn action_bar(&self) -> Line<'a> {
+        let mut spans = Vec::new();
+        let actions = self
+            .actions
+            .iter()
+            .map(|x| (key_name(x.key), x.label))
+            .chain(
+                self.fallback
+                    .iter()
+                    .map(|(label, _)| ("any".to_owned(), *label)),
+            );
+        for (key, label) in actions {
+            spans.extend([
+                format!(" <{key}>").green().bold(),
+                format!(" {label} ").into(),
+            ]);
+        }
+        Line::from(spans)
+    }
+}
+
+impl Widget for Popup<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .border_type(self.border_type)
+            .title(self.title.clone())
+            .title_bottom(self.action_bar().centered());
+
+        Clear.render(area, buf);
+        Paragraph::new(self.body)
+            .block(block)
+            .wrap(Wrap { trim: false })
+            .centered()
+            .render(area, buf);
+    }
+}
+
+fn key_name(key: KeyCode) -> String {
+    match key {
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Enter => "Enter".to_owned(),
+        KeyCode::Esc => "Esc".to_owned(),
+        KeyCode::Backspace => "Backspace".to_owned(),
+        key => format!("{key:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, ratatui::crossterm::event::KeyModifiers};
+
+    fn popup<'a>() -> Popup<'a> {
+        Popup::new(
+            " Saved ",
+            vec!["Download successful!".into(), "42 KiB".into()],
+        )
+        .action(KeyCode::Char('o'), "Open", Message::OpenDownloadFolder)
+        .action(KeyCode::Char('c'), "Copy", Message::CopyDownloadPath)
+        .fallback("Dismiss", Message::DismissDownloadPrompt)
+    }
+
+    fn press(popup: Popup, code: KeyCode) -> Option<Message> {
+        popup.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn rendered(popup: Popup, width: u16, height: u16) -> Buffer {
+        let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+        let area = popup.area(buf.area);
+        popup.render(area, &mut buf);
+        buf
+    }
+
+    fn lines(buf: &Buffer) -> Vec<String> {
+        (0..buf.area.height)
+            .map(|y| {
+                (0..buf.area.width)
+                    .map(|x| buf.get(x, y).symbol())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn renders_the_actions_along_the_bottom() {
+        let buf = rendered(popup(), 40, 6);
+        assert_eq!(
+            lines(&buf),
+            [
+                "                                        ",
+                "  ┏ Saved ━━━━━━━━━━━━━━━━━━━━━━━━━━━━┓ ",
+                "  ┃       Download successful!        ┃ ",
+                "  ┃              42 KiB               ┃ ",
+                "  ┗ <o> Open  <c> Copy  <any> Dismiss ┛ ",
+                "                                        ",
+            ]
+        );
+        let key = buf.get(4, 4).style();
+        assert_eq!(key.fg, Some(Color::Green));
+        assert!(key.add_modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn routes_keys_to_their_actions() {
+        assert!(matches!(
+            press(popup(), KeyCode::Char('o')),
+            Some(Message::OpenDownloadFolder)
+        ));
+        assert!(matches!(
+            press(popup(), KeyCode::Char('c')),
+            Some(Message::CopyDownloadPath)
+        ));
+        for code in [KeyCode::Char('x'), KeyCode::Enter, KeyCode::Esc] {
+            assert!(
+                matches!(press(popup(), code), Some(Message::DismissDownloadPrompt)),
+                "{code:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_other_keys_without_a_fallback() {
+        let popup = || Popup::new("", "Body").action(KeyCode::Enter, "Ok", Message::Exit);
+        assert!(matches!(
+            press(popup(), KeyCode::Enter),
+            Some(Message::Exit)
+        ));
+        assert!(press(popup(), KeyCode::Char('x')).is_none());
+    }
+}